@@ -0,0 +1,88 @@
+#!/usr/bin/env python3
+#
+# This file is part of Poki Launcher.
+#
+# Poki Launcher is free software: you can redistribute it and/or modify
+# it under the terms of the GNU General Public License as published by
+# the Free Software Foundation, either version 3 of the License, or
+# (at your option) any later version.
+#
+# Poki Launcher is distributed in the hope that it will be useful,
+# but WITHOUT ANY WARRANTY; without even the implied warranty of
+# MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+# GNU General Public License for more details.
+#
+# You should have received a copy of the GNU General Public License
+# along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+#
+"""Example script plugin: a calculator.
+
+Type `=1+2*3` to see the result, select it to copy it to the clipboard.
+Requests are read from stdin and answered on stdout, one JSON object per
+line.  See lib-poki-launcher/src/plugins/script.rs for the protocol.
+"""
+import ast
+import json
+import operator
+import subprocess
+import sys
+
+OPERATORS = {
+    ast.Add: operator.add,
+    ast.Sub: operator.sub,
+    ast.Mult: operator.mul,
+    ast.Div: operator.truediv,
+    ast.Pow: operator.pow,
+    ast.Mod: operator.mod,
+    ast.USub: operator.neg,
+}
+
+last_result = None
+
+
+def evaluate(node):
+    if isinstance(node, ast.Expression):
+        return evaluate(node.body)
+    if isinstance(node, ast.Constant) and isinstance(node.value, (int, float)):
+        return node.value
+    if isinstance(node, ast.BinOp):
+        op = OPERATORS[type(node.op)]
+        return op(evaluate(node.left), evaluate(node.right))
+    if isinstance(node, ast.UnaryOp):
+        return OPERATORS[type(node.op)](evaluate(node.operand))
+    raise ValueError("unsupported expression")
+
+
+def search(params):
+    global last_result
+    try:
+        last_result = str(evaluate(ast.parse(params["query"], mode="eval")))
+    except Exception:
+        return {"items": []}
+    return {"items": [{"id": 1, "name": last_result, "score": 1.0}]}
+
+
+def run(params):
+    if last_result is not None:
+        subprocess.run(["xclip", "-selection", "clipboard"],
+                       input=last_result.encode(), check=False)
+
+
+def handle(method, params):
+    if method == "init":
+        return {"prefix": params["config"].get("prefix", "=")}
+    if method == "search":
+        return search(params)
+    if method == "run":
+        return run(params)
+    return None
+
+
+for line in sys.stdin:
+    request = json.loads(line)
+    try:
+        response = {"id": request["id"],
+                    "result": handle(request["method"], request["params"])}
+    except Exception as e:
+        response = {"id": request["id"], "error": str(e)}
+    print(json.dumps(response), flush=True)
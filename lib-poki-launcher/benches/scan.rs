@@ -0,0 +1,73 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use lib_poki_launcher::frecency_db::FrecencyDB;
+use lib_poki_launcher::scan::DesktopFiles;
+use std::fs;
+use std::path::PathBuf;
+
+const NUM_FILES: usize = 10_000;
+
+/// Write the desktop files to scan, unless an earlier run already did.
+fn fixture() -> PathBuf {
+    let dir = std::env::temp_dir().join("poki-launcher-scan-bench");
+    let complete = dir.join(".complete");
+    if complete.exists() {
+        return dir;
+    }
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..NUM_FILES {
+        let entry = format!(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=App {i}\n\
+             Name[de]=Anwendung {i}\n\
+             Comment=Generated app number {i}\n\
+             Exec=/usr/bin/app-{i} %U\n\
+             Icon=app-{i}\n\
+             Categories=Utility;\n",
+            i = i
+        );
+        fs::write(dir.join(format!("app-{}.desktop", i)), entry).unwrap();
+    }
+    fs::write(&complete, "").unwrap();
+    dir
+}
+
+fn scan(c: &mut Criterion) {
+    let paths = vec![fixture().to_string_lossy().into_owned()];
+    let mut group = c.benchmark_group("scan_10k");
+    group.sample_size(10);
+    group.bench_function("parse", |b| {
+        b.iter(|| DesktopFiles::default().scan(&paths))
+    });
+    let mut files = DesktopFiles::default();
+    files.scan(&paths);
+    group.bench_function("unchanged", |b| b.iter(|| files.scan(&paths)));
+    let apps = files.apps();
+    group.bench_function("merge", |b| {
+        b.iter_batched(
+            || FrecencyDB::new(":memory:").unwrap(),
+            |mut db| db.merge_new_entries(&apps).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,80 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lib_poki_launcher::frecency_db::{DBItem, FrecencyDB};
+use serde_derive::{Deserialize, Serialize};
+
+const NUM_ITEMS: usize = 100_000;
+const WORDS: &[&str] = &[
+    "report", "notes", "budget", "draft", "photo", "invoice", "project",
+    "readme", "backup", "config", "final", "meeting", "summary", "todo",
+    "letter", "resume", "slides", "data", "test", "archive",
+];
+const EXTENSIONS: &[&str] = &["txt", "md", "pdf", "odt", "png", "rs", "csv"];
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+struct Item(String);
+
+impl DBItem for Item {
+    fn get_sort_string(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Generate file name like items from a simple LCG so runs are comparable.
+fn items() -> Vec<Item> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |n: usize| {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) as usize % n
+    };
+    (0..NUM_ITEMS)
+        .map(|i| {
+            Item(format!(
+                "{}_{}_{}.{}",
+                WORDS[next(WORDS.len())],
+                WORDS[next(WORDS.len())],
+                i,
+                EXTENSIONS[next(EXTENSIONS.len())]
+            ))
+        })
+        .collect()
+}
+
+fn search(c: &mut Criterion) {
+    let items = items();
+    let mut db: FrecencyDB<Item> = FrecencyDB::new(":memory:").unwrap();
+    db.merge_new_entries(&items).unwrap();
+    // Give some items a history so the frecency ordering matters
+    for item in items.iter().step_by(97) {
+        let id = db.get_ranked_list(&item.0, Some(1)).unwrap()[0].id;
+        db.update_score(id).unwrap();
+    }
+
+    let mut group = c.benchmark_group("search_100k");
+    for query in &["r", "rep", "budnot", "final_summary", "zzz"] {
+        group.bench_function(*query, |b| {
+            b.iter(|| db.get_ranked_list(black_box(query), Some(5)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, search);
+criterion_main!(benches);
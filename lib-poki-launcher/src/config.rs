@@ -19,6 +19,7 @@ use anyhow::Error;
 use directories::ProjectDirs;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::default::Default;
 use std::fs::create_dir;
 use std::path::PathBuf;
@@ -43,13 +44,57 @@ pub struct FileOptions {
     pub selected_app_color: String,
     pub app_text_color: String,
     pub app_separator_color: String,
+    /// Color of the characters of an item that matched the search.
+    pub highlight_color: String,
 
     pub input_font_size: i32,
     pub app_font_size: i32,
     pub input_box_ratio: f32,
 
     pub plugin_load_order: Vec<String>,
+    /// Options of each plugin, by plugin name.
+    ///
+    /// Read by the plugins into their own option types.
     pub plugins: Value,
+    /// Map of alias to the name of the item it should bring to the top.
+    pub aliases: HashMap<String, String>,
+    /// Keyboard layouts to retry a search with when it finds nothing.
+    pub keyboard_layouts: Vec<String>,
+    /// Keyboard layouts defined by the user, by name.
+    pub custom_keyboard_layouts: HashMap<String, LayoutMapOptions>,
+    /// Search all the plugins and merge their results unless the search
+    /// starts with a plugin's prefix.
+    pub blend_results: bool,
+    /// How each plugin's results are blended, by plugin name.
+    pub blend: HashMap<String, BlendOptions>,
+}
+
+/// How a plugin's results are blended with the results of the others.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlendOptions {
+    /// Multiplier for the scores of the plugin's results.
+    pub weight: f64,
+    /// Most results of the plugin to show.
+    pub max_items: Option<usize>,
+}
+
+impl Default for BlendOptions {
+    fn default() -> Self {
+        BlendOptions {
+            weight: 1.0,
+            max_items: None,
+        }
+    }
+}
+
+/// A user defined keyboard layout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutMapOptions {
+    /// Characters typed by the keys with this layout.
+    pub from: String,
+    /// Characters typed by the same keys with a QWERTY layout.
+    pub to: String,
 }
 
 impl Default for FileOptions {
@@ -65,22 +110,19 @@ impl Default for FileOptions {
             selected_app_color: "#44475a".into(),
             app_text_color: "#f8f8f2".into(),
             app_separator_color: "#bd93f9".into(),
+            highlight_color: "#ff79c6".into(),
 
             input_font_size: 13,
             app_font_size: 20,
             input_box_ratio: 0.1,
 
             plugin_load_order: vec!["apps".into()],
-            plugins: json!({
-                "apps": {
-                    "app_paths": [
-                        "/usr/share/applications",
-                        "~/.local/share/applications/",
-                        "/var/lib/snapd/desktop/applications",
-                        "/var/lib/flatpak/exports/share/applications"
-                    ]
-                }
-            }),
+            plugins: json!({}),
+            aliases: HashMap::new(),
+            keyboard_layouts: vec!["russian".into(), "greek".into()],
+            custom_keyboard_layouts: HashMap::new(),
+            blend_results: false,
+            blend: HashMap::new(),
         }
     }
 }
@@ -92,7 +134,7 @@ impl Config {
         let config_dir = DIRS.config_dir();
         let file_path = config_dir.join("poki-launcher.hjson");
         if !config_dir.exists() {
-            create_dir(&config_dir)?;
+            create_dir(config_dir)?;
         }
 
         let file_options = if file_path.as_path().exists() {
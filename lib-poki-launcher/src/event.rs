@@ -1,4 +1,9 @@
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Event {
+    /// Reload all the plugins.
     Reload,
+    /// Search again because a plugin's results changed.
+    Refresh,
+    /// Show an error reported by a plugin.
+    Error(String),
 }
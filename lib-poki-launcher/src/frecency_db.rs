@@ -14,43 +14,175 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
+use crate::matcher::{self, Match, Matcher};
+use crate::normalize::{fold, SearchText};
+use crate::query::Query;
 use anyhow::{Context, Result};
-use fuzzy_matcher::skim::fuzzy_match;
 use log::*;
 use rmp_serde as rmp;
 use rusqlite::{params, Connection, OptionalExtension, NO_PARAMS};
 use serde::{de, Serialize};
+use std::cmp::{Ordering, Reverse};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::hash::Hasher;
-use std::marker::PhantomData;
 use std::path::Path;
 use std::process;
 use std::time::SystemTime;
 use thiserror::Error;
 
+/// The ranking score of a perfect match, relative to the frecency score.
+///
+/// Matchers score items between 0 and 1 and that is scaled by this
+/// before being added to the frecency score.
+const MATCH_WEIGHT: f64 = 100.0;
+
+/// Searches finding fewer items than this also look for items matching
+/// with typos by default.
+const DEFAULT_TYPO_FALLBACK: usize = 3;
+
 /// An apps database.
+///
+/// The items are kept in memory and searched there, the database
+/// is only used to persist them between runs.
 #[derive(Debug)]
 pub struct FrecencyDB<T: DBItem> {
     /// The list of apps.
     conn: Connection,
+    /// In memory copy of the items in the database.
+    entries: Vec<Entry<T>>,
+    /// Indexes into `entries` sorted by frecency score, highest first.
+    by_score: Vec<usize>,
+    /// Map of item id to its index in `entries`.
+    by_id: HashMap<u64, usize>,
+    /// Ids of the pinned items.
+    pinned: HashSet<u64>,
     /// The reference time used in the ranking calculations.
     reference_time: f64,
     /// The half life of the app launches
     half_life: f64,
-    _ph: PhantomData<T>,
+    /// Aliases from the config file resolved to item ids.
+    config_aliases: HashMap<String, u64>,
+    /// Algorithms used to match search strings against the items.
+    matchers: Vec<Box<dyn Matcher>>,
+    /// Gets other names an item can be found by.
+    alternate_names: Option<fn(&T) -> Vec<String>>,
+    /// Searches finding fewer items than this also look for items
+    /// matching with typos.
+    typo_fallback: usize,
+}
+
+/// An item in the in memory index.
+#[derive(Debug)]
+struct Entry<T: DBItem> {
+    id: u64,
+    score: f64,
+    /// The sort string of the item prepared for matching.
+    text: SearchText,
+    /// Other names of the item prepared for matching.
+    alternate_texts: Vec<SearchText>,
+    item: T,
+}
+
+/// The items of a rescan prepared to replace the items of a database.
+///
+/// Preparing the items for matching is slow so it can be done without
+/// holding the database, which `swap_entries` then only needs for a
+/// moment.
+pub struct NewEntries<T: DBItem> {
+    entries: Vec<Entry<T>>,
+    /// The serialized items, in the same order as `entries`.
+    data: Vec<Vec<u8>>,
+    alternate_names: Option<fn(&T) -> Vec<String>>,
+}
+
+impl<T: DBItem> NewEntries<T> {
+    /// Prepare items for matching, with the other names given by
+    /// `alternate_names`.
+    pub fn new(
+        items: Vec<T>,
+        alternate_names: Option<fn(&T) -> Vec<String>>,
+    ) -> Result<Self> {
+        let data = items
+            .iter()
+            .map(rmp::to_vec)
+            .collect::<Result<Vec<_>, _>>()?;
+        let entries = items
+            .into_iter()
+            .map(|item| Entry {
+                id: item_id(&item),
+                score: 0.0,
+                text: SearchText::new(item.get_sort_string()),
+                alternate_texts: alternate_texts(alternate_names, &item),
+                item,
+            })
+            .collect();
+        Ok(NewEntries {
+            entries,
+            data,
+            alternate_names,
+        })
+    }
 }
 
 pub struct Container<T: DBItem> {
     pub id: u64,
+    /// The score the item was ranked by.
+    pub score: f64,
+    /// Indices of the characters of the sort string that matched the search.
+    pub indices: Vec<usize>,
+    /// Whether the item is pinned.
+    pub pinned: bool,
     pub item: T,
 }
 
-pub trait DBItem: Serialize + de::DeserializeOwned + Hash {
+pub trait DBItem: Serialize + de::DeserializeOwned + Hash + Clone {
     fn get_sort_string(&self) -> &str;
 }
 
+/// The id an item is stored under.
+fn item_id(item: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Write an item to the database, keeping the score of the row if there
+/// is one, and return its score.
+fn write_entry(conn: &Connection, id: u64, item: &impl DBItem) -> Result<f64> {
+    let data = rmp::to_vec(item)?;
+    conn.execute(
+        "INSERT OR IGNORE INTO main (id, score, sort_text, data)
+         VALUES (?, 0.0, ?, ?);",
+        params![id as i64, item.get_sort_string(), data],
+    )?;
+    conn.execute(
+        "UPDATE main SET sort_text=?, data=? WHERE id=?;",
+        params![item.get_sort_string(), data, id as i64],
+    )?;
+    Ok(conn.query_row(
+        "SELECT score FROM main WHERE id=?;",
+        params![id as i64],
+        |row| row.get(0),
+    )?)
+}
+
+/// Prepare the alternate names of an item for matching.
+fn alternate_texts<T>(
+    alternate_names: Option<fn(&T) -> Vec<String>>,
+    item: &T,
+) -> Vec<SearchText> {
+    match alternate_names {
+        Some(alternate_names) => alternate_names(item)
+            .iter()
+            .map(|name| SearchText::new(name))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
 fn update_frecency(
     score: f64,
     weight: f64,
@@ -61,6 +193,38 @@ fn update_frecency(
     (score / exp + weight) * exp
 }
 
+/// An item's index into `entries` ordered by its ranking score.
+#[derive(Debug, Clone)]
+struct Ranked {
+    score: f64,
+    idx: usize,
+    indices: Vec<usize>,
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .partial_cmp(&other.score)
+            .unwrap_or(Ordering::Equal)
+            // Prefer items that come first in `entries` on a tie
+            .then(other.idx.cmp(&self.idx))
+    }
+}
+
 macro_rules! table_def {
     ($input:expr, $tmp:expr) => {
         format!(
@@ -85,22 +249,169 @@ impl<T: DBItem> FrecencyDB<T> {
             .context(FrecencyDBError::OpenDB(db_path_str.clone()))?;
         conn.pragma_update(None, "temp_store", &"MEMORY")?;
         conn.execute(&table_def!("main", false), NO_PARAMS)?;
-        conn.create_scalar_function("calc_score", 3, true, |ctx| {
-            let f_score = ctx.get::<f64>(0)?;
-            let text = ctx.get::<String>(1)?;
-            let search = ctx.get::<String>(2)?;
-            Ok(match fuzzy_match(&text, &search) {
-                Some(score) if score > 0 => score as f64 + f_score,
-                _ => 0.0,
-            })
-        })?;
-        Ok(FrecencyDB {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS aliases (
+              alias       TEXT PRIMARY KEY NOT NULL,
+              id          INT NOT NULL
+          );",
+            NO_PARAMS,
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pinned (
+              id          INT PRIMARY KEY NOT NULL
+          );",
+            NO_PARAMS,
+        )?;
+        let mut db = FrecencyDB {
             conn,
+            entries: Vec::new(),
+            by_score: Vec::new(),
+            by_id: HashMap::new(),
+            pinned: HashSet::new(),
             reference_time: current_time_secs(),
             // Half life of 3 days
             half_life: 60.0 * 60.0 * 24.0 * 3.0,
-            _ph: PhantomData,
-        })
+            config_aliases: HashMap::new(),
+            matchers: matcher::default_matchers(),
+            alternate_names: None,
+            typo_fallback: DEFAULT_TYPO_FALLBACK,
+        };
+        db.load_index()?;
+        Ok(db)
+    }
+
+    /// Load the items in the database into memory.
+    ///
+    /// Rows that can't be read, such as ones saved by an older version,
+    /// are skipped until the next rescan replaces them.
+    fn load_index(&mut self) -> Result<()> {
+        let alternate_names = self.alternate_names;
+        let rows: Result<Vec<(i64, f64, Vec<u8>)>, _> = self
+            .conn
+            .prepare("SELECT id, score, data FROM main")?
+            .query_map(NO_PARAMS, |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect();
+        let entries = rows?
+            .into_iter()
+            .filter_map(|(id, score, data)| {
+                let item: T = match rmp::from_slice(&data) {
+                    Ok(item) => item,
+                    Err(e) => {
+                        warn!("Skipping unreadable item {}: {}", id, e);
+                        return None;
+                    }
+                };
+                Some(Entry {
+                    id: id as u64,
+                    score,
+                    text: SearchText::new(item.get_sort_string()),
+                    alternate_texts: alternate_texts(alternate_names, &item),
+                    item,
+                })
+            })
+            .collect();
+        let pinned: Result<HashSet<_>, _> = self
+            .conn
+            .prepare("SELECT id FROM pinned")?
+            .query_map(NO_PARAMS, |row| row.get::<_, i64>(0))?
+            .map(|id| id.map(|id| id as u64))
+            .collect();
+        self.pinned = pinned?;
+        self.set_entries(entries);
+        debug!("Loaded {} items into the index", self.entries.len());
+        Ok(())
+    }
+
+    /// Replace the items in memory, indexing them by id and score.
+    fn set_entries(&mut self, entries: Vec<Entry<T>>) {
+        self.entries = entries;
+        self.by_id = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| (entry.id, idx))
+            .collect();
+        self.sort_by_score();
+    }
+
+    /// Sort `by_score` after the scores have changed.
+    fn sort_by_score(&mut self) {
+        let entries = &self.entries;
+        let mut by_score: Vec<usize> = (0..entries.len()).collect();
+        by_score.sort_by(|&a, &b| {
+            entries[b]
+                .score
+                .partial_cmp(&entries[a].score)
+                .unwrap_or(Ordering::Equal)
+        });
+        self.by_score = by_score;
+    }
+
+    fn container(
+        &self,
+        idx: usize,
+        score: f64,
+        indices: Vec<usize>,
+    ) -> Container<T> {
+        let entry = &self.entries[idx];
+        Container {
+            id: entry.id,
+            score,
+            indices,
+            pinned: self.pinned.contains(&entry.id),
+            item: entry.item.clone(),
+        }
+    }
+
+    /// Set the algorithms used to match search strings against the items.
+    ///
+    /// An item's match score is the best score any of the matchers give it.
+    pub fn set_matchers(&mut self, matchers: Vec<Box<dyn Matcher>>) {
+        self.matchers = matchers;
+    }
+
+    /// Set how few items a search has to find for items matching with
+    /// typos to be added after them.  Zero turns typo matching off.
+    pub fn set_typo_fallback(&mut self, typo_fallback: usize) {
+        self.typo_fallback = typo_fallback;
+    }
+
+    /// Set the function giving the other names an item can be found by,
+    /// such as transliterations of its name.
+    ///
+    /// Matches against these names aren't highlighted since they aren't
+    /// the text shown for the item.
+    pub fn set_alternate_names(
+        &mut self,
+        alternate_names: Option<fn(&T) -> Vec<String>>,
+    ) {
+        self.alternate_names = alternate_names;
+        for entry in &mut self.entries {
+            entry.alternate_texts =
+                alternate_texts(alternate_names, &entry.item);
+        }
+    }
+
+    /// Match an item's sort string and alternate names with `matches`,
+    /// returning the best match.
+    fn match_entry(
+        &self,
+        entry: &Entry<T>,
+        matches: impl Fn(&SearchText) -> Option<Match>,
+    ) -> Option<Match> {
+        let text = matches(&entry.text);
+        entry.alternate_texts.iter().filter_map(&matches).fold(
+            text,
+            |best, m| match best {
+                Some(best) if best.score >= m.score => Some(best),
+                _ => Some(Match {
+                    score: m.score,
+                    indices: Vec::new(),
+                }),
+            },
+        )
     }
 
     /// Seconds elapsed since the reference time.
@@ -115,18 +426,22 @@ impl<T: DBItem> FrecencyDB<T> {
     /// * `uuid` - The uuid of the app to update.
     /// * `weight` - The amount to update to score by.
     pub fn update_score(&mut self, id: u64) -> Result<()> {
-        let score: f64 = self.conn.query_row(
-            "SELECT * FROM main WHERE id = ?",
-            &[id as i64],
-            |row| row.get(1),
-        )?;
+        let idx = match self.by_id.get(&id) {
+            Some(idx) => *idx,
+            None => return Ok(()),
+        };
+        let score = update_frecency(
+            self.entries[idx].score,
+            1.0,
+            self.secs_elapsed(),
+            self.half_life,
+        );
         self.conn.execute(
-             "UPDATE main SET score=? WHERE id=?;",
-             params![
-                 update_frecency(score, 1.0, self.secs_elapsed(), self.half_life,) as f64,
-                 id as i64,
-             ],
-         )?;
+            "UPDATE main SET score=? WHERE id=?;",
+            params![score, id as i64],
+        )?;
+        self.entries[idx].score = score;
+        self.sort_by_score();
         Ok(())
     }
 
@@ -134,29 +449,38 @@ impl<T: DBItem> FrecencyDB<T> {
     ///
     /// * Apps in `self` that are not in `apps_to_merge` will be removed from `self`
     /// * Apps in `apps_to_merge` not in `self` will be added to `self`
-    pub fn merge_new_entries(
-        &mut self,
-        items_to_merge: &[impl DBItem],
-    ) -> Result<()> {
-        self.conn.execute_batch(&format!(
-            "BEGIN;{}{}COMMIT;",
+    /// * Apps in both keep their score but take the data of the new app,
+    ///   since an item's id only depends on part of it and the rest, like
+    ///   the size of a file or the icon of an app, can change
+    pub fn merge_new_entries(&mut self, items_to_merge: &[T]) -> Result<()> {
+        let new =
+            NewEntries::new(items_to_merge.to_vec(), self.alternate_names)?;
+        self.swap_entries(new)
+    }
+
+    /// Replace the items with the items of a re-scan prepared by
+    /// `NewEntries::new`, keeping the scores like `merge_new_entries`.
+    ///
+    /// The alternate names the items were prepared with replace the ones
+    /// set with `set_alternate_names`.
+    pub fn swap_entries(&mut self, new: NewEntries<T>) -> Result<()> {
+        // A single transaction so the rows aren't written one at a time
+        let tx = self.conn.transaction()?;
+        tx.execute_batch(&format!(
+            "{}{}",
             table_def!("new", true),
             table_def!("tmp", false)
         ))?;
-        let mut insert = self
-             .conn
-             .prepare("INSERT INTO new (id, score, sort_text, data) VALUES (?, 0.0, ?, ?);")?;
-        for item in items_to_merge {
-            let mut hasher = DefaultHasher::new();
-            item.hash(&mut hasher);
-            let id = hasher.finish() as i64;
-            let sort_text = item.get_sort_string();
-            let data = rmp::to_vec(&item)?;
-            insert.execute(params![id, sort_text, data])?;
+        let mut insert = tx.prepare(
+            "INSERT INTO new (id, score, sort_text, data) VALUES (?, 0.0, ?, ?);",
+        )?;
+        for (entry, data) in new.entries.iter().zip(&new.data) {
+            let sort_text = entry.item.get_sort_string();
+            insert.execute(params![entry.id as i64, sort_text, data])?;
         }
-        self.conn.execute_batch(
+        drop(insert);
+        tx.execute_batch(
             "
-             BEGIN;
              INSERT INTO tmp
              SELECT
                  new.id,
@@ -164,70 +488,407 @@ impl<T: DBItem> FrecencyDB<T> {
                  THEN main.score
                  ELSE 0.0
                  END AS score,
-                 CASE WHEN main.sort_text IS NOT NULL
-                 THEN main.sort_text
-                 ELSE new.sort_text
-                 END AS sort_text,
-                 CASE WHEN main.data IS NOT NULL
-                 THEN main.data
-                 ELSE new.data
-                 END AS data
+                 new.sort_text,
+                 new.data
              FROM new LEFT OUTER JOIN main
              ON new.id = main.id;
              DROP TABLE main;
              DROP TABLE new;
-             ALTER TABLE tmp RENAME TO main;
-             COMMIT;",
+             ALTER TABLE tmp RENAME TO main;",
         )?;
+        tx.commit()?;
+        let mut entries = new.entries;
+        for entry in &mut entries {
+            if let Some(&idx) = self.by_id.get(&entry.id) {
+                entry.score = self.entries[idx].score;
+            }
+        }
+        self.alternate_names = new.alternate_names;
+        self.set_entries(entries);
+        debug!("Swapped in {} items", self.entries.len());
+        Ok(())
+    }
+
+    /// Add an item or update the data of an item that's already there,
+    /// returning its id.
+    pub fn upsert_entry(&mut self, item: T) -> Result<u64> {
+        let id = item_id(&item);
+        let score = write_entry(&self.conn, id, &item)?;
+        self.index_entry(id, score, item);
+        self.sort_by_score();
+        Ok(id)
+    }
+
+    /// Add or update several items at once, like `upsert_entry`.
+    ///
+    /// The items are written in one transaction and only change the index
+    /// once it's committed, so a failed write leaves both as they were.
+    pub fn upsert_entries(
+        &mut self,
+        items: impl IntoIterator<Item = T>,
+    ) -> Result<()> {
+        let items: Vec<(u64, T)> = items
+            .into_iter()
+            .map(|item| (item_id(&item), item))
+            .collect();
+        let tx = self.conn.transaction()?;
+        let scores = items
+            .iter()
+            .map(|(id, item)| write_entry(&tx, *id, item))
+            .collect::<Result<Vec<_>>>()?;
+        tx.commit()?;
+        for ((id, item), score) in items.into_iter().zip(scores) {
+            self.index_entry(id, score, item);
+        }
+        self.sort_by_score();
+        Ok(())
+    }
+
+    /// Add or update an item in memory without sorting the entries again.
+    fn index_entry(&mut self, id: u64, score: f64, item: T) {
+        let text = SearchText::new(item.get_sort_string());
+        let alternate_texts = alternate_texts(self.alternate_names, &item);
+        if let Some(&idx) = self.by_id.get(&id) {
+            let entry = &mut self.entries[idx];
+            entry.text = text;
+            entry.alternate_texts = alternate_texts;
+            entry.item = item;
+            return;
+        }
+        self.by_id.insert(id, self.entries.len());
+        self.entries.push(Entry {
+            id,
+            score,
+            text,
+            alternate_texts,
+            item,
+        });
+    }
+
+    /// Remove an item from the index and the database.
+    pub fn remove_entry(&mut self, item: &T) -> Result<()> {
+        let id = item_id(item);
+        self.remove_entries_by_id(&[id])
+    }
+
+    /// Remove the items matching a predicate, like `remove_entry`.
+    pub fn remove_entries(
+        &mut self,
+        remove: impl Fn(&T) -> bool,
+    ) -> Result<()> {
+        let ids: Vec<u64> = self
+            .entries
+            .iter()
+            .filter(|entry| remove(&entry.item))
+            .map(|entry| entry.id)
+            .collect();
+        self.remove_entries_by_id(&ids)
+    }
+
+    /// Delete items in one transaction, then remove them from the index.
+    fn remove_entries_by_id(&mut self, ids: &[u64]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction()?;
+        for &id in ids {
+            tx.execute("DELETE FROM main WHERE id = ?;", params![id as i64])?;
+        }
+        tx.commit()?;
+        let ids: HashSet<u64> = ids.iter().copied().collect();
+        if self.entries.iter().any(|entry| ids.contains(&entry.id)) {
+            let mut entries = std::mem::take(&mut self.entries);
+            entries.retain(|entry| !ids.contains(&entry.id));
+            self.set_entries(entries);
+        }
         Ok(())
     }
 
     /// Get the apps in rank order for a given search string.
     ///
-    /// This ranks the apps both by frecency score and fuzzy search.
-    // TODO Remove num_items
+    /// This ranks the apps both by frecency score and how well they match
+    /// the search string.
+    ///
+    /// The search can use the extended search syntax of `Query`.
+    /// If fewer items than the typo fallback are found for a plain search,
+    /// items matching it with a few typos are added after them.
     pub fn get_ranked_list(
         &self,
         search: &str,
         num_items: Option<usize>,
     ) -> Result<Vec<Container<T>>> {
-        let mut stmt = self.conn.prepare(
-            "
-         SELECT
-         id, data, calc_score(score, sort_text, ?) as sort_score
-         FROM main
-         WHERE sort_score > 0
-         ORDER BY sort_score DESC",
-        )?;
-        let item_iter = stmt.query_map(params![search], |row| {
-            let id: i64 = row.get(0)?;
-            let data: Vec<u8> = row.get(1)?;
-            let obj: T = rmp::from_slice(&data).unwrap();
-            Ok(Container {
-                id: id as u64,
-                item: obj,
+        self.get_ranked_list_where(search, num_items, |_| true)
+    }
+
+    /// Get the items `keep` returns true for in rank order for a given
+    /// search string, like `get_ranked_list`.
+    ///
+    /// Items are filtered before they're matched so filtering out most
+    /// items makes the search faster.
+    pub fn get_ranked_list_where(
+        &self,
+        search: &str,
+        num_items: Option<usize>,
+        keep: impl Fn(&T) -> bool,
+    ) -> Result<Vec<Container<T>>> {
+        let aliased = match self.get_alias(search.trim())? {
+            Some(id) => self.by_id.get(&id).copied(),
+            None => None,
+        }
+        .filter(|&idx| keep(&self.entries[idx].item));
+        let limit = num_items.unwrap_or(self.entries.len());
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let search = fold(search);
+        let query = Query::parse(&search);
+        let mut ranked = self.top_matches(limit, |idx, entry| {
+            if Some(idx) == aliased || !keep(&entry.item) {
+                return None;
+            }
+            match &query {
+                Some(query) => self.match_entry(entry, |text| {
+                    query.matches(&self.matchers, text)
+                }),
+                None => self.match_entry(entry, |text| {
+                    matcher::best_match(&self.matchers, text, &search)
+                }),
+            }
+        });
+        if query.is_none() && ranked.len() < self.typo_fallback.min(limit) {
+            let found: HashSet<usize> = ranked
+                .iter()
+                .map(|ranked| ranked.idx)
+                .chain(aliased)
+                .collect();
+            let typos = self.top_matches(limit - ranked.len(), |idx, entry| {
+                if found.contains(&idx) || !keep(&entry.item) {
+                    return None;
+                }
+                matcher::typo_match(&entry.text, &search)
+            });
+            ranked.extend(typos);
+        }
+        let mut list: Vec<_> = ranked
+            .into_iter()
+            .map(|ranked| {
+                self.container(ranked.idx, ranked.score, ranked.indices)
             })
-        })?;
-        let res: Result<Vec<_>, _> = match num_items {
-            Some(num) => item_iter.take(num).collect(),
-            None => item_iter.collect(),
-        };
-        Ok(res?)
+            .collect();
+        if let Some(idx) = aliased {
+            // Aliases are scored as perfect matches
+            let score = MATCH_WEIGHT + self.entries[idx].score;
+            list.insert(0, self.container(idx, score, Vec::new()));
+            list.truncate(limit);
+        }
+        Ok(list)
     }
 
-    pub fn get_by_id(&self, id: u64) -> Result<Option<Container<T>>> {
+    /// Find the `limit` best matching items, best first.
+    ///
+    /// Items are visited highest frecency first so that once `limit`
+    /// items have been found, the search can stop at the first item
+    /// whose frecency is too low for it to beat any of them.
+    fn top_matches(
+        &self,
+        limit: usize,
+        matches: impl Fn(usize, &Entry<T>) -> Option<Match>,
+    ) -> Vec<Ranked> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        // Min heap of the best items found so far
+        let mut best: BinaryHeap<Reverse<Ranked>> = BinaryHeap::new();
+        for &idx in &self.by_score {
+            let entry = &self.entries[idx];
+            if best.len() >= limit {
+                let Reverse(worst) = best.peek().unwrap();
+                if entry.score + MATCH_WEIGHT < worst.score {
+                    break;
+                }
+            }
+            let m = match matches(idx, entry) {
+                Some(m) => m,
+                None => continue,
+            };
+            let score = m.score * MATCH_WEIGHT + entry.score;
+            best.push(Reverse(Ranked {
+                score,
+                idx,
+                indices: m.indices,
+            }));
+            if best.len() > limit {
+                best.pop();
+            }
+        }
+        let mut ranked: Vec<_> =
+            best.into_iter().map(|Reverse(ranked)| ranked).collect();
+        ranked.sort_unstable_by(|a, b| b.cmp(a));
+        ranked
+    }
+
+    /// Scale the score of a search result to between 0 and 1, where 1 is
+    /// a perfect match of the item with the highest frecency.
+    pub fn normalize_score(&self, score: f64) -> f64 {
+        let top = self
+            .by_score
+            .first()
+            .map(|&idx| self.entries[idx].score.max(0.0))
+            .unwrap_or(0.0);
+        (score / (MATCH_WEIGHT + top)).clamp(0.0, 1.0)
+    }
+
+    /// Get the items `keep` returns true for, highest frecency score first.
+    ///
+    /// Unlike `get_top_ranked` this includes pinned items and items that
+    /// have never been used.
+    pub fn get_top_where(
+        &self,
+        num_items: usize,
+        keep: impl Fn(&T) -> bool,
+    ) -> Vec<Container<T>> {
+        self.by_score
+            .iter()
+            .copied()
+            .filter(|&idx| keep(&self.entries[idx].item))
+            .take(num_items)
+            .map(|idx| self.container(idx, self.entries[idx].score, Vec::new()))
+            .collect()
+    }
+
+    /// Get the items with the highest frecency score, ignoring pinned items.
+    ///
+    /// Items that have never been used aren't returned.
+    pub fn get_top_ranked(
+        &self,
+        num_items: usize,
+    ) -> Result<Vec<Container<T>>> {
         Ok(self
-            .conn
-            .query_row("SELECT * FROM main WHERE id = ?", &[id as i64], |row| {
-                let id: i64 = row.get(0)?;
-                let data: Vec<u8> = row.get(3)?;
-                let obj: T = rmp::from_slice(&data).unwrap();
-                Ok(Container {
-                    id: id as u64,
-                    item: obj,
-                })
+            .by_score
+            .iter()
+            .copied()
+            .take_while(|&idx| self.entries[idx].score > 0.0)
+            .filter(|&idx| !self.pinned.contains(&self.entries[idx].id))
+            .take(num_items)
+            .map(|idx| self.container(idx, self.entries[idx].score, Vec::new()))
+            .collect())
+    }
+
+    /// Get the pinned items, highest frecency score first.
+    pub fn get_pinned(&self) -> Result<Vec<Container<T>>> {
+        Ok(self
+            .by_score
+            .iter()
+            .filter(|&&idx| self.pinned.contains(&self.entries[idx].id))
+            .map(|&idx| {
+                self.container(idx, self.entries[idx].score, Vec::new())
             })
-            .optional()?)
+            .collect())
+    }
+
+    /// Whether an item is pinned.
+    pub fn is_pinned(&self, id: u64) -> bool {
+        self.pinned.contains(&id)
+    }
+
+    /// Pin an item if it isn't pinned, otherwise unpin it.
+    ///
+    /// Returns true if the item is now pinned.
+    pub fn toggle_pinned(&mut self, id: u64) -> Result<bool> {
+        if self.pinned.remove(&id) {
+            self.conn.execute(
+                "DELETE FROM pinned WHERE id = ?;",
+                params![id as i64],
+            )?;
+            return Ok(false);
+        }
+        self.conn.execute(
+            "INSERT INTO pinned (id) VALUES (?);",
+            params![id as i64],
+        )?;
+        self.pinned.insert(id);
+        Ok(true)
+    }
+
+    /// Add an alias for an item, replacing the item it previously pointed to.
+    pub fn add_alias(&mut self, alias: &str, id: u64) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO aliases (alias, id) VALUES (?, ?);",
+            params![alias, id as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Remove an alias added with `add_alias`.
+    pub fn remove_alias(&mut self, alias: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM aliases WHERE alias = ?;", &[alias])?;
+        Ok(())
+    }
+
+    /// Get the id of the item an alias points to.
+    ///
+    /// Aliases from the config file take precedence over ones stored in
+    /// the database.
+    pub fn get_alias(&self, alias: &str) -> Result<Option<u64>> {
+        if alias.is_empty() {
+            return Ok(None);
+        }
+        if let Some(id) = self.config_aliases.get(alias) {
+            return Ok(Some(*id));
+        }
+        let id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM aliases WHERE alias = ?",
+                &[alias],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id.map(|id| id as u64))
+    }
+
+    /// Resolve the aliases from the config file against the items in the
+    /// database.
+    ///
+    /// # Arguments
+    ///
+    /// * `aliases` - Map of alias to the sort string of the item it points to.
+    ///
+    /// Aliases whose target isn't in this database are ignored since
+    /// another plugin may own that item.
+    pub fn set_config_aliases(
+        &mut self,
+        aliases: &HashMap<String, String>,
+    ) -> Result<()> {
+        let mut config_aliases = HashMap::new();
+        for (alias, target) in aliases {
+            let entry = self
+                .entries
+                .iter()
+                .find(|entry| entry.item.get_sort_string() == target);
+            if let Some(entry) = entry {
+                trace!("Alias `{}` resolved to `{}`", alias, target);
+                config_aliases.insert(alias.clone(), entry.id);
+            }
+        }
+        self.config_aliases = config_aliases;
+        Ok(())
+    }
+
+    pub fn get_by_id(&self, id: u64) -> Result<Option<Container<T>>> {
+        Ok(self.by_id.get(&id).map(|&idx| {
+            self.container(idx, self.entries[idx].score, Vec::new())
+        }))
+    }
+
+    /// Number of items in the database.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 }
 
@@ -256,6 +917,7 @@ pub enum FrecencyDBError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde::Deserialize;
 
     impl DBItem for String {
         fn get_sort_string(&self) -> &str {
@@ -263,6 +925,256 @@ mod tests {
         }
     }
 
+    fn test_db(items: &[&str]) -> FrecencyDB<String> {
+        let mut db = FrecencyDB::new(":memory:").unwrap();
+        let items: Vec<String> = items.iter().map(|s| s.to_string()).collect();
+        db.merge_new_entries(&items).unwrap();
+        db
+    }
+
+    fn names(list: Vec<Container<String>>) -> Vec<String> {
+        list.into_iter().map(|cont| cont.item).collect()
+    }
+
+    fn id_of(db: &FrecencyDB<String>, name: &str) -> u64 {
+        db.get_ranked_list(name, None)
+            .unwrap()
+            .into_iter()
+            .find(|cont| cont.item == name)
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn alternate_names() {
+        let mut db = test_db(&["Firefox", "Gimp"]);
+        assert!(db.get_ranked_list("pmig", None).unwrap().is_empty());
+        db.set_alternate_names(Some(|item: &String| {
+            vec![item.chars().rev().collect()]
+        }));
+        let list = db.get_ranked_list("pmig", None).unwrap();
+        assert_eq!(names(list), vec!["Gimp"]);
+        let list = db.get_ranked_list("gimp", None).unwrap();
+        assert_eq!(list[0].indices, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn typo_fallback() {
+        let mut db = test_db(&["Firefox", "Thunderbird", "Fire Starter"]);
+        let list = db.get_ranked_list("fire", None).unwrap();
+        assert_eq!(list.len(), 2);
+        let list = db.get_ranked_list("thunderbrid", None).unwrap();
+        assert_eq!(names(list), vec!["Thunderbird"]);
+        // Typo matches come after the real matches
+        let list = db.get_ranked_list("firef", None).unwrap();
+        assert_eq!(names(list), vec!["Firefox", "Fire Starter"]);
+        db.set_typo_fallback(0);
+        assert!(db.get_ranked_list("fierfox", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn extended_query() {
+        let db = test_db(&["Firefox", "Firefox Developer", "Fire Starter"]);
+        let list = db.get_ranked_list("^fire !dev", None).unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(names(list).iter().all(|name| !name.contains("Dev")));
+        let list = db.get_ranked_list("er$", None).unwrap();
+        assert_eq!(names(list), vec!["Firefox Developer", "Fire Starter"]);
+    }
+
+    #[test]
+    fn filtered_search() {
+        let mut db = test_db(&["Firefox", "Fire Starter", "Files"]);
+        db.update_score(id_of(&db, "Files")).unwrap();
+        let short = |name: &String| name.len() < 10;
+        // `Files` is a typo match
+        let list = db.get_ranked_list_where("fire", None, short).unwrap();
+        assert_eq!(names(list), vec!["Firefox", "Files"]);
+        let list = db.get_ranked_list_where("fierfox", None, |_| false);
+        assert!(list.unwrap().is_empty());
+        assert_eq!(names(db.get_top_where(5, short)), vec!["Files", "Firefox"]);
+    }
+
+    #[test]
+    fn normalized_scores() {
+        let mut db = test_db(&["Firefox", "Files", "Gimp"]);
+        db.update_score(id_of(&db, "Files")).unwrap();
+        let scores: Vec<f64> = db
+            .get_ranked_list("fi", None)
+            .unwrap()
+            .into_iter()
+            .map(|cont| db.normalize_score(cont.score))
+            .collect();
+        assert_eq!(scores.len(), 2);
+        assert!(scores[0] > scores[1]);
+        assert!(scores.iter().all(|&score| score > 0.0 && score <= 1.0));
+    }
+
+    #[test]
+    fn alias_ranks_first() {
+        let mut db = test_db(&["Firefox", "Fish Fillets", "Off"]);
+        let id = id_of(&db, "Firefox");
+        db.add_alias("of", id).unwrap();
+        let out = names(db.get_ranked_list("of", None).unwrap());
+        assert_eq!(out[0], "Firefox");
+        assert_eq!(out.iter().filter(|s| *s == "Firefox").count(), 1);
+    }
+
+    #[test]
+    fn config_alias_overrides_db_alias() {
+        let mut db = test_db(&["Firefox", "Thunderbird"]);
+        let id = id_of(&db, "Thunderbird");
+        db.add_alias("ff", id).unwrap();
+        let mut aliases = HashMap::new();
+        aliases.insert("ff".to_owned(), "Firefox".to_owned());
+        aliases.insert("missing".to_owned(), "Not Installed".to_owned());
+        db.set_config_aliases(&aliases).unwrap();
+        let out = names(db.get_ranked_list("ff", None).unwrap());
+        assert_eq!(out[0], "Firefox");
+        assert_eq!(db.get_alias("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn top_ranked_and_pinned() {
+        let mut db = test_db(&["Firefox", "Thunderbird", "Terminal"]);
+        let firefox = id_of(&db, "Firefox");
+        let terminal = id_of(&db, "Terminal");
+        db.update_score(firefox).unwrap();
+        db.update_score(firefox).unwrap();
+        db.update_score(terminal).unwrap();
+        assert_eq!(
+            names(db.get_top_ranked(5).unwrap()),
+            vec!["Firefox", "Terminal"]
+        );
+
+        assert!(db.toggle_pinned(terminal).unwrap());
+        assert_eq!(names(db.get_pinned().unwrap()), vec!["Terminal"]);
+        assert_eq!(names(db.get_top_ranked(5).unwrap()), vec!["Firefox"]);
+        assert!(!db.toggle_pinned(terminal).unwrap());
+        assert!(db.get_pinned().unwrap().is_empty());
+    }
+
+    /// An item whose id only depends on its name, like apps and files.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Versioned {
+        name: String,
+        version: u32,
+    }
+
+    impl Hash for Versioned {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.name.hash(state);
+        }
+    }
+
+    impl DBItem for Versioned {
+        fn get_sort_string(&self) -> &str {
+            &self.name
+        }
+    }
+
+    #[test]
+    fn merge_takes_new_data() {
+        let item = |version| Versioned {
+            name: "Firefox".to_owned(),
+            version,
+        };
+        let mut db = FrecencyDB::new(":memory:").unwrap();
+        db.merge_new_entries(&[item(1)]).unwrap();
+        let id = item_id(&item(1));
+        db.update_score(id).unwrap();
+        db.merge_new_entries(&[item(2)]).unwrap();
+        let top = db.get_top_ranked(5).unwrap();
+        assert_eq!(top[0].item, item(2));
+        db.load_index().unwrap();
+        let top = db.get_top_ranked(5).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].item, item(2));
+    }
+
+    #[test]
+    fn swap_entries_keeps_scores() {
+        let mut db = test_db(&["Firefox", "Thunderbird"]);
+        let firefox = id_of(&db, "Firefox");
+        db.update_score(firefox).unwrap();
+        let items = vec!["Firefox".to_owned(), "Terminal".to_owned()];
+        let new = NewEntries::new(items, None).unwrap();
+        db.swap_entries(new).unwrap();
+        let top = db.get_top_ranked(5).unwrap();
+        assert_eq!(names(top), vec!["Firefox"]);
+        assert!(db.get_ranked_list("thunderbird", None).unwrap().is_empty());
+        assert_eq!(db.len(), 2);
+        // The database agrees with the swapped in items
+        db.load_index().unwrap();
+        assert_eq!(names(db.get_top_ranked(5).unwrap()), vec!["Firefox"]);
+        assert_eq!(db.len(), 2);
+    }
+
+    #[test]
+    fn upsert_and_remove_entries() {
+        let mut db = test_db(&["Firefox", "Thunderbird"]);
+        let firefox = id_of(&db, "Firefox");
+        db.update_score(firefox).unwrap();
+        assert_eq!(db.upsert_entry("Firefox".to_owned()).unwrap(), firefox);
+        assert_eq!(names(db.get_top_ranked(5).unwrap()), vec!["Firefox"]);
+        let items = vec!["Terminal".to_owned(), "Files".to_owned()];
+        db.upsert_entries(items).unwrap();
+        assert_eq!(db.len(), 4);
+
+        db.remove_entry(&"Firefox".to_owned()).unwrap();
+        assert!(db.get_ranked_list("firefox", None).unwrap().is_empty());
+        db.remove_entries(|item| item.starts_with('T')).unwrap();
+        assert_eq!(db.len(), 1);
+        // The database agrees with the index
+        db.load_index().unwrap();
+        assert_eq!(db.len(), 1);
+        assert_eq!(id_of(&db, "Files"), item_id(&"Files".to_owned()));
+        assert!(db.get_top_ranked(5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn limit_matches_full_ranking() {
+        let items: Vec<String> = (0..200)
+            .map(|i| {
+                format!("item {} {}", i, if i % 3 == 0 { "abc" } else { "" })
+            })
+            .collect();
+        let mut db = FrecencyDB::new(":memory:").unwrap();
+        db.merge_new_entries(&items).unwrap();
+        for i in (0..200).step_by(7) {
+            let id = id_of(&db, &items[i]);
+            for _ in 0..(i % 5) {
+                db.update_score(id).unwrap();
+            }
+        }
+        let full = names(db.get_ranked_list("abc", None).unwrap());
+        let limited = names(db.get_ranked_list("abc", Some(5)).unwrap());
+        assert_eq!(full.len(), 67);
+        assert_eq!(&full[..5], &limited[..]);
+    }
+
+    #[test]
+    fn unreadable_rows_are_skipped() {
+        let mut db = test_db(&["Firefox", "Thunderbird"]);
+        db.conn
+            .execute(
+                "UPDATE main SET data = ? WHERE sort_text = 'Firefox';",
+                params![vec![0xc1u8]],
+            )
+            .unwrap();
+        db.load_index().unwrap();
+        assert_eq!(db.len(), 1);
+        assert!(db.get_ranked_list("firefox", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn remove_alias() {
+        let mut db = test_db(&["Firefox"]);
+        db.add_alias("web", 1).unwrap();
+        db.remove_alias("web").unwrap();
+        assert_eq!(db.get_alias("web").unwrap(), None);
+    }
+
     // #[test]
     // fn big() {
     //     let mut db: FrecencyDB<String> = FrecencyDB::new("test.db").unwrap();
@@ -0,0 +1,182 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::config::LayoutMapOptions;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// Built in layouts as the characters of their letter keys followed by
+/// the characters of the same keys on a US QWERTY keyboard.
+const BUILTIN_LAYOUTS: &[(&str, &str, &str)] = &[
+    (
+        "russian",
+        "йцукенгшщзхъфывапролджэячсмитьбюё\
+         ЙЦУКЕНГШЩЗХЪФЫВАПРОЛДЖЭЯЧСМИТЬБЮЁ",
+        "qwertyuiop[]asdfghjkl;'zxcvbnm,.`\
+         QWERTYUIOP{}ASDFGHJKL:\"ZXCVBNM<>~",
+    ),
+    (
+        "ukrainian",
+        "йцукенгшщзхїфівапролджєячсмитьбю\
+         ЙЦУКЕНГШЩЗХЇФІВАПРОЛДЖЄЯЧСМИТЬБЮ",
+        "qwertyuiop[]asdfghjkl;'zxcvbnm,.\
+         QWERTYUIOP{}ASDFGHJKL:\"ZXCVBNM<>",
+    ),
+    (
+        "greek",
+        "ςερτυθιοπασδφγηξκλζχψωβνμάέήίόύώ\
+         ΕΡΤΥΘΙΟΠΑΣΔΦΓΗΞΚΛΖΧΨΩΒΝΜΆΈΉΊΌΎΏ",
+        "wertyuiopasdfghjklzxcvbnmaehioyv\
+         ERTYUIOPASDFGHJKLZXCVBNMAEHIOYV",
+    ),
+];
+
+/// Maps the characters typed with a keyboard layout to the characters
+/// the same keys type with a US QWERTY layout.
+#[derive(Debug, Clone)]
+pub struct KeyboardLayout {
+    pub name: String,
+    map: HashMap<char, char>,
+}
+
+impl KeyboardLayout {
+    /// Create a layout mapping each character of `from` to the
+    /// character at the same position of `to`.
+    pub fn new(name: &str, from: &str, to: &str) -> Result<Self> {
+        if from.chars().count() != to.chars().count() {
+            return Err(anyhow!(
+                "The keyboard layout {} maps {} characters to {} characters",
+                name,
+                from.chars().count(),
+                to.chars().count()
+            ));
+        }
+        Ok(KeyboardLayout {
+            name: name.to_owned(),
+            map: from.chars().zip(to.chars()).collect(),
+        })
+    }
+
+    /// Get one of the layouts built into the launcher.
+    pub fn builtin(name: &str) -> Option<Self> {
+        BUILTIN_LAYOUTS
+            .iter()
+            .find(|(builtin, _, _)| *builtin == name)
+            .map(|(name, from, to)| KeyboardLayout::new(name, from, to))
+            .and_then(Result::ok)
+    }
+
+    /// Get the layouts to retry searches with from the config file.
+    ///
+    /// Layouts defined in the config file take precedence over the built
+    /// in layouts of the same name.
+    pub fn from_config(
+        names: &[String],
+        custom: &HashMap<String, LayoutMapOptions>,
+    ) -> (Vec<Self>, Vec<anyhow::Error>) {
+        let mut layouts = Vec::new();
+        let mut errors = Vec::new();
+        for name in names {
+            let layout = match custom.get(name) {
+                Some(options) => {
+                    KeyboardLayout::new(name, &options.from, &options.to)
+                }
+                None => KeyboardLayout::builtin(name)
+                    .ok_or_else(|| anyhow!("Unknown keyboard layout {}", name)),
+            };
+            match layout {
+                Ok(layout) => layouts.push(layout),
+                Err(e) => errors.push(e),
+            }
+        }
+        (layouts, errors)
+    }
+
+    /// Convert text typed with this layout to what it would have been
+    /// with a QWERTY layout.
+    ///
+    /// Returns `None` if none of the characters are from this layout.
+    pub fn convert(&self, text: &str) -> Option<String> {
+        let mut changed = false;
+        let converted = text
+            .chars()
+            .map(|c| match self.map.get(&c) {
+                Some(&qwerty) => {
+                    changed = true;
+                    qwerty
+                }
+                None => c,
+            })
+            .collect();
+        if changed {
+            Some(converted)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_layouts() {
+        for (name, _, _) in BUILTIN_LAYOUTS {
+            assert!(KeyboardLayout::builtin(name).is_some(), "{}", name);
+        }
+        assert!(KeyboardLayout::builtin("dvorak").is_none());
+    }
+
+    #[test]
+    fn convert() {
+        let russian = KeyboardLayout::builtin("russian").unwrap();
+        assert_eq!(russian.convert("ашкуащч").as_deref(), Some("firefox"));
+        assert_eq!(russian.convert("Пшьз 2").as_deref(), Some("Gimp 2"));
+        assert_eq!(russian.convert("firefox"), None);
+        let greek = KeyboardLayout::builtin("greek").unwrap();
+        assert_eq!(greek.convert("τθτ").as_deref(), Some("tut"));
+    }
+
+    #[test]
+    fn custom_layouts() {
+        let mut custom = HashMap::new();
+        custom.insert(
+            "russian".to_owned(),
+            LayoutMapOptions {
+                from: "ф".to_owned(),
+                to: "z".to_owned(),
+            },
+        );
+        custom.insert(
+            "broken".to_owned(),
+            LayoutMapOptions {
+                from: "abc".to_owned(),
+                to: "x".to_owned(),
+            },
+        );
+        let names = vec![
+            "russian".to_owned(),
+            "broken".to_owned(),
+            "greek".to_owned(),
+            "missing".to_owned(),
+        ];
+        let (layouts, errors) = KeyboardLayout::from_config(&names, &custom);
+        assert_eq!(layouts.len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(layouts[0].convert("ф").as_deref(), Some("z"));
+    }
+}
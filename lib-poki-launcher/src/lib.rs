@@ -14,23 +14,46 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
+/// Cancel searches that are no longer needed
+pub mod cancel;
 /// Application configuration
 pub mod config;
 
 pub mod event;
-mod frecency_db;
+/// Item database ranked by frecency and fuzzy search
+pub mod frecency_db;
+/// Correct searches typed with the wrong keyboard layout
+pub mod keyboard_layout;
+/// Algorithms for matching search strings
+pub mod matcher;
+/// Text normalization for matching
+pub mod normalize;
 mod plugins;
+/// Extended search syntax
+pub mod query;
 mod run;
+/// Temporary directories for tests
+#[cfg(test)]
+mod test_dir;
 
+/// Scan for desktop entries
+pub use self::plugins::apps::scan;
+
+use self::cancel::CancelToken;
 use self::config::Config;
 use self::event::Event;
-use self::plugins::Plugin;
+use self::keyboard_layout::KeyboardLayout;
+use self::plugins::{Plugin, PluginMode};
+use self::query::Query;
 use anyhow::{anyhow, Error, Result};
 use directories::{BaseDirs, ProjectDirs};
 use lazy_static::lazy_static;
 use log::{debug, error};
+use std::cmp::Ordering;
+use std::ops::Range;
 use std::path::PathBuf;
 use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
 
 /// Things that you'll probably need in include when using this lib
 pub mod prelude {
@@ -50,59 +73,337 @@ lazy_static! {
 
 pub struct PokiLauncher {
     pub config: Config,
-    plugins: Vec<Box<dyn Plugin>>,
-    selected_plugin: Option<usize>,
+    plugins: Vec<Arc<dyn Plugin>>,
+    /// How each plugin is picked to search.
+    modes: Vec<PluginMode>,
+    /// The plugin searches are limited to, switched with `next_mode`.
+    active_mode: Option<usize>,
+    /// Layouts to retry searches that find nothing with.
+    keyboard_layouts: Vec<KeyboardLayout>,
+    /// How the last search was corrected, if it was.
+    correction: Option<Correction>,
+    /// Held while the plugins are reloading.
+    reloading: Arc<Mutex<()>>,
+}
+
+/// A search that was retried as if it was typed with another keyboard
+/// layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Correction {
+    /// Name of the layout the search was typed with.
+    pub layout: String,
+    /// The search that was used instead.
+    pub query: String,
 }
 
 impl PokiLauncher {
     pub fn init() -> Result<(PokiLauncher, Vec<Error>)> {
         let config = Config::load()?;
-        let (plugins, errors) = self::plugins::init_plugins(&config);
+        let (plugins, modes, mut errors) = self::plugins::init_plugins(&config);
+        let (keyboard_layouts, layout_errors) = KeyboardLayout::from_config(
+            &config.file_options.keyboard_layouts,
+            &config.file_options.custom_keyboard_layouts,
+        );
+        errors.extend(layout_errors);
         Ok((
             PokiLauncher {
                 config,
                 plugins,
-                selected_plugin: None,
+                modes,
+                active_mode: None,
+                keyboard_layouts,
+                correction: None,
+                reloading: Arc::new(Mutex::new(())),
             },
             errors,
         ))
     }
 
+    /// Search the plugins the input is routed to.
+    ///
+    /// Finds nothing once `cancel` is cancelled, so a search that has been
+    /// replaced by a newer one stops as soon as it can.
     pub fn search(
         &mut self,
         input: &str,
         num_items: usize,
+        cancel: &CancelToken,
     ) -> Result<Vec<ListItem>> {
-        for (i, plugin) in self.plugins.iter().enumerate() {
-            if plugin.matcher(&self.config, &input) {
-                self.selected_plugin = Some(i);
-                debug!("Selecting plugin {}", i);
-                return plugin.search(&self.config, &input, num_items);
+        self.correction = None;
+        if input.is_empty() && self.active_mode.is_none() {
+            let all: Vec<usize> = (0..self.plugins.len()).collect();
+            return self.top_items(&all, num_items);
+        }
+        let (targets, query) = self.route(input);
+        if query.is_empty() {
+            return self.top_items(&targets, num_items);
+        }
+        let list = self.search_plugins(input, num_items, cancel)?;
+        if !list.is_empty() || cancel.is_cancelled() {
+            return Ok(list);
+        }
+        // A layout can turn a letter into syntax, like `э` into `'`, which
+        // the user didn't type
+        let extended = Query::is_extended(input);
+        let corrections: Vec<Correction> = self
+            .keyboard_layouts
+            .iter()
+            .filter_map(|layout| {
+                layout.convert(input).map(|query| Correction {
+                    layout: layout.name.clone(),
+                    query: if extended {
+                        query
+                    } else {
+                        Query::strip_syntax(&query)
+                    },
+                })
+            })
+            .collect();
+        for correction in corrections {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let list =
+                self.search_plugins(&correction.query, num_items, cancel)?;
+            if !list.is_empty() {
+                debug!("Corrected search {} to {:?}", input, correction);
+                self.correction = Some(correction);
+                return Ok(list);
             }
         }
         Ok(vec![])
     }
 
-    pub fn run(&mut self, id: u64) -> Result<()> {
-        let selected = self.selected_plugin.take();
-        match selected {
-            Some(selected) => self.plugins[selected].run(&self.config, id),
-            None => Err(anyhow!("No app selected")),
+    /// How the last search was corrected for the wrong keyboard layout.
+    ///
+    /// `None` if the search found items as it was typed.
+    pub fn correction(&self) -> Option<&Correction> {
+        self.correction.as_ref()
+    }
+
+    /// Switch to searching only the next plugin, or back to searching
+    /// normally after the last plugin.
+    pub fn next_mode(&mut self) {
+        self.active_mode = match self.active_mode {
+            None if !self.plugins.is_empty() => Some(0),
+            Some(i) if i + 1 < self.plugins.len() => Some(i + 1),
+            _ => None,
+        };
+    }
+
+    /// Switch to searching only the previous plugin, or back to searching
+    /// normally before the first plugin.
+    pub fn prev_mode(&mut self) {
+        self.active_mode = match self.active_mode {
+            None => self.plugins.len().checked_sub(1),
+            Some(0) => None,
+            Some(i) => Some(i - 1),
+        };
+    }
+
+    /// Name of the plugin searches are limited to.
+    ///
+    /// `None` if searches pick plugins by their prefixes.
+    pub fn mode(&self) -> Option<&str> {
+        self.active_mode.map(|i| self.plugins[i].name())
+    }
+
+    /// Pick the plugins to search and remove the prefix from the input.
+    ///
+    /// Only the active mode's plugin is searched if there is one.
+    /// Otherwise input starting with a plugin's prefix only searches that
+    /// plugin and other input searches the default plugins.
+    fn route<'a>(&self, input: &'a str) -> (Vec<usize>, &'a str) {
+        let strip = |i: usize| {
+            self.modes[i]
+                .prefix
+                .as_ref()
+                .and_then(|prefix| input.strip_prefix(prefix.as_str()))
+        };
+        if let Some(i) = self.active_mode {
+            return (vec![i], strip(i).unwrap_or(input));
+        }
+        for i in 0..self.plugins.len() {
+            if let Some(rest) = strip(i) {
+                return (vec![i], rest);
+            }
         }
+        let defaults = (0..self.plugins.len())
+            .filter(|&i| self.modes[i].default)
+            .collect();
+        (defaults, input)
     }
 
-    pub fn reload(&mut self) -> Result<()> {
-        for plugin in &mut self.plugins {
-            if let Err(e) = plugin.reload(&self.config) {
-                error!("{:?}", e);
+    /// Search with the plugins the input is routed to.
+    ///
+    /// The results of several default plugins are only blended if
+    /// configured to, otherwise the first one is searched.
+    fn search_plugins(
+        &mut self,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ListItem>> {
+        let (targets, input) = self.route(input);
+        if targets.len() > 1 && self.config.file_options.blend_results {
+            return Ok(self.blended_search(&targets, input, num_items, cancel));
+        }
+        let i = match targets.first() {
+            Some(&i) => i,
+            None => return Ok(vec![]),
+        };
+        debug!("Selecting plugin {}", self.plugins[i].name());
+        let list =
+            self.plugins[i].search(&self.config, input, num_items, cancel)?;
+        Ok(self.record_sources(list.into_iter().map(|item| (i, item))))
+    }
+
+    /// Search several plugins, merging their results by score.
+    fn blended_search(
+        &mut self,
+        targets: &[usize],
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Vec<ListItem> {
+        let mut items = Vec::new();
+        for &i in targets {
+            if cancel.is_cancelled() {
+                return Vec::new();
+            }
+            let plugin = &self.plugins[i];
+            let options = self
+                .config
+                .file_options
+                .blend
+                .get(plugin.name())
+                .cloned()
+                .unwrap_or_default();
+            let max_items =
+                options.max_items.unwrap_or(num_items).min(num_items);
+            match plugin.blended_search(&self.config, input, max_items, cancel)
+            {
+                Ok(list) => {
+                    items.extend(list.into_iter().map(|item| {
+                        (i, item.score * options.weight, item.item)
+                    }))
+                }
+                Err(e) => error!("{:?}", e),
             }
         }
-        Ok(())
+        items.sort_by(|(_, a, _), (_, b, _)| {
+            b.partial_cmp(a).unwrap_or(Ordering::Equal)
+        });
+        items.truncate(num_items);
+        self.record_sources(items.into_iter().map(|(i, _, item)| (i, item)))
+    }
+
+    /// Get the pinned and most used items of some plugins.
+    fn top_items(
+        &mut self,
+        targets: &[usize],
+        num_items: usize,
+    ) -> Result<Vec<ListItem>> {
+        let mut items = Vec::new();
+        for &i in targets {
+            match self.plugins[i].top_items(&self.config, num_items) {
+                Ok(list) => {
+                    items.extend(list.into_iter().map(|item| (i, item)))
+                }
+                Err(e) => error!("{:?}", e),
+            }
+        }
+        items.sort_by(|(_, a), (_, b)| {
+            b.pinned
+                .cmp(&a.pinned)
+                .then(b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal))
+        });
+        items.truncate(num_items);
+        Ok(self
+            .record_sources(items.into_iter().map(|(i, item)| (i, item.item))))
+    }
+
+    /// Label the items with the name of the plugin they came from, which
+    /// is passed back with their id to run them.
+    fn record_sources(
+        &self,
+        items: impl Iterator<Item = (usize, ListItem)>,
+    ) -> Vec<ListItem> {
+        items
+            .map(|(i, mut item)| {
+                item.source = self.plugins[i].name().to_owned();
+                item
+            })
+            .collect()
+    }
+
+    /// Get the index of the plugin an item came from by its `source`.
+    fn source_plugin(&self, source: &str) -> Result<usize> {
+        self.plugins
+            .iter()
+            .position(|plugin| plugin.name() == source)
+            .ok_or_else(|| anyhow!("No plugin named `{}`", source))
+    }
+
+    /// Run an item of the plugin named `source`.
+    pub fn run(&mut self, source: &str, id: u64) -> Result<()> {
+        let selected = self.source_plugin(source)?;
+        self.plugins[selected].run(&self.config, id)
+    }
+
+    /// Add an alias for an item.
+    pub fn add_alias(
+        &mut self,
+        alias: &str,
+        source: &str,
+        id: u64,
+    ) -> Result<()> {
+        let selected = self.source_plugin(source)?;
+        self.plugins[selected].add_alias(&self.config, alias, id)
+    }
+
+    /// Get the secondary actions that can be run on an item.
+    pub fn actions(&self, source: &str, id: u64) -> Result<Vec<Action>> {
+        let selected = self.source_plugin(source)?;
+        self.plugins[selected].actions(&self.config, id)
+    }
+
+    /// Run a secondary action on an item.
+    pub fn run_action(
+        &mut self,
+        source: &str,
+        id: u64,
+        action: &str,
+    ) -> Result<ActionResult> {
+        let selected = self.source_plugin(source)?;
+        self.plugins[selected].run_action(&self.config, id, action)
+    }
+
+    /// Pin or unpin an item.
+    ///
+    /// Returns true if the item is now pinned.
+    pub fn toggle_pin(&mut self, source: &str, id: u64) -> Result<bool> {
+        let selected = self.source_plugin(source)?;
+        self.plugins[selected].toggle_pin(&self.config, id)
+    }
+
+    pub fn reload(&self) -> Result<()> {
+        self.reloader().reload()
+    }
+
+    /// Get a handle to reload the plugins with, so the launcher doesn't
+    /// have to stay borrowed during a reload.
+    pub fn reloader(&self) -> Reloader {
+        Reloader {
+            config: self.config.clone(),
+            plugins: self.plugins.clone(),
+            reloading: self.reloading.clone(),
+        }
     }
 
     pub fn register_event_handlers(&mut self) -> Receiver<Event> {
         let (event_tx, event_rx) = mpsc::channel();
-        for plugin in &mut self.plugins {
+        for plugin in &self.plugins {
             let tx = event_tx.clone();
             plugin.register_event_handlers(&self.config, tx);
         }
@@ -111,9 +412,94 @@ impl PokiLauncher {
     }
 }
 
+/// Reloads the plugins of a launcher.
+///
+/// The plugins keep being searched while they reload, with their new items
+/// swapped in once they're ready.
+pub struct Reloader {
+    config: Config,
+    plugins: Vec<Arc<dyn Plugin>>,
+    reloading: Arc<Mutex<()>>,
+}
+
+impl Reloader {
+    /// Reload the plugins, waiting for any other reload to finish first.
+    pub fn reload(&self) -> Result<()> {
+        let _reloading = self.reloading.lock().expect("Reload Mutex poisoned");
+        for plugin in &self.plugins {
+            if let Err(e) = plugin.reload(&self.config) {
+                error!("{:?}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A secondary action that can be run on an item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    /// Identifies the action to the plugin.
+    pub id: String,
+    /// Shown in the list of actions.
+    pub name: String,
+}
+
+impl Action {
+    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Self {
+        Action {
+            id: id.into(),
+            name: name.into(),
+        }
+    }
+}
+
+/// What the launcher should do after running an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionResult {
+    /// Hide the launcher.
+    Hide,
+    /// Copy text to the clipboard and hide the launcher.
+    Copy(String),
+    /// Stay open and search again.
+    Refresh,
+}
+
 #[derive(Debug, Clone)]
 pub struct ListItem {
     pub name: String,
     pub icon: String,
     pub id: u64,
+    /// Ranges of the characters of `name` that matched the search.
+    pub matches: Vec<Range<usize>>,
+    /// Second line describing the item, ex. the directory of a file.
+    pub subtitle: String,
+    /// Name of the plugin the item came from, which runs it.
+    pub source: String,
+    /// Labels telling apart items with the same name.
+    pub badges: Vec<Badge>,
+}
+
+/// A label shown next to an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Badge {
+    /// An app installed with flatpak.
+    Flatpak,
+    /// An app installed with snap.
+    Snap,
+    /// An app that runs in a terminal.
+    Terminal,
+    /// A pinned item.
+    Pinned,
+}
+
+impl Badge {
+    /// Text of the badge.
+    pub fn label(self) -> &'static str {
+        match self {
+            Badge::Flatpak => "flatpak",
+            Badge::Snap => "snap",
+            Badge::Terminal => "terminal",
+            Badge::Pinned => "pinned",
+        }
+    }
 }
@@ -0,0 +1,580 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::normalize::SearchText;
+use fuzzy_matcher::skim::fuzzy_indices;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::ops::Range;
+
+// Bonuses the skim matcher gives a matched character, copied from
+// `fuzzy_matcher::skim` which doesn't export them.
+const SKIM_BONUS_MATCHED: f64 = 4.0;
+const SKIM_BONUS_CASE_MATCH: f64 = 4.0;
+const SKIM_BONUS_UPPER_MATCH: f64 = 6.0;
+const SKIM_BONUS_ADJACENCY: f64 = 10.0;
+const SKIM_BONUS_SEPARATOR: f64 = 8.0;
+const SKIM_BONUS_CAMEL: f64 = 8.0;
+
+/// More than the skim matcher can give a single character of the search
+/// string, used to scale its scores to between 0 and 1.
+///
+/// This is the sum of all its bonuses so no score is cut off at 1.
+const MAX_SKIM_CHAR_SCORE: f64 = SKIM_BONUS_MATCHED
+    + SKIM_BONUS_CASE_MATCH
+    + SKIM_BONUS_UPPER_MATCH
+    + SKIM_BONUS_ADJACENCY
+    + SKIM_BONUS_SEPARATOR
+    + SKIM_BONUS_CAMEL;
+
+/// Score of typing the initials of every word of a name, below the 1.0 of
+/// typing the name itself.
+const ALL_INITIALS_SCORE: f64 = 0.9;
+
+/// How well a text matched a search string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    /// Score between 0 and 1 where 1 is a perfect match.
+    pub score: f64,
+    /// Indices of the characters of the text that matched.
+    pub indices: Vec<usize>,
+}
+
+impl Match {
+    fn new(score: f64, indices: Vec<usize>) -> Self {
+        Match { score, indices }
+    }
+}
+
+/// An algorithm for matching a search string against the text of an item.
+pub trait Matcher: Debug + Send + Sync {
+    /// Match `text` against `search`.
+    ///
+    /// `search` must already be folded with `normalize::fold` and the indices of
+    /// the match are into the folded text.
+    /// Returns `None` if `text` doesn't match.
+    fn matches(&self, text: &SearchText, search: &str) -> Option<Match>;
+}
+
+/// The matchers that can be selected in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatcherKind {
+    /// Skim fuzzy matching, the same algorithm fzf uses.
+    Fuzzy,
+    /// The search string appears in the text.
+    Substring,
+    /// Each word of the search string starts a word of the text.
+    Prefix,
+    /// The search string matches the first letters of the text's words.
+    Initials,
+    /// Fuzzy matching of file paths favouring the file name.
+    Path,
+}
+
+impl MatcherKind {
+    pub fn matcher(self) -> Box<dyn Matcher> {
+        match self {
+            MatcherKind::Fuzzy => Box::new(Fuzzy),
+            MatcherKind::Substring => Box::new(Substring),
+            MatcherKind::Prefix => Box::new(WordPrefix),
+            MatcherKind::Initials => Box::new(Initials),
+            MatcherKind::Path => Box::new(PathFuzzy),
+        }
+    }
+}
+
+/// The matchers used when none are set in the config file.
+pub fn default_matchers() -> Vec<Box<dyn Matcher>> {
+    vec![MatcherKind::Fuzzy.matcher()]
+}
+
+/// Match `text` with each matcher, returning the best scoring match.
+///
+/// `search` must already be folded and the indices of the returned match
+/// are into the original text.
+pub fn best_match(
+    matchers: &[Box<dyn Matcher>],
+    text: &SearchText,
+    search: &str,
+) -> Option<Match> {
+    matchers
+        .iter()
+        .filter_map(|matcher| matcher.matches(text, search))
+        .fold(None, |best: Option<Match>, m| match best {
+            Some(best) if best.score >= m.score => Some(best),
+            _ => Some(m),
+        })
+        .map(|m| Match::new(m.score, text.original_indices(&m.indices)))
+}
+
+/// Merge sorted character indices into ranges of consecutive characters.
+pub fn index_ranges(indices: &[usize]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for &idx in indices {
+        match ranges.last_mut() {
+            Some(range) if range.end == idx => range.end += 1,
+            _ => ranges.push(idx..idx + 1),
+        }
+    }
+    ranges
+}
+
+/// Check if the characters of `search` appear in `text` in order.
+///
+/// This is much cheaper than fuzzy matching and any text it rejects
+/// would also be rejected by the fuzzy matcher.
+fn is_subsequence(text: &str, search: &str) -> bool {
+    let mut text = text.chars();
+    search.chars().all(|s| text.by_ref().any(|t| t == s))
+}
+
+/// Split a folded search string into words.
+fn search_words(search: &str) -> Vec<Vec<char>> {
+    search
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.chars().collect())
+        .collect()
+}
+
+/// The number of typos allowed in a word of a search string.
+fn max_typos(word_len: usize) -> usize {
+    match word_len {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// Damerau-Levenshtein distance between two strings, counting a swap of
+/// two adjacent characters as a single edit.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    // Rows of the distance matrix for the last two and current
+    // prefixes of `a`
+    let mut prev2: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur: Vec<usize> = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// Match the words of `search` against the words of `text` allowing a
+/// few typos in each.
+///
+/// `fierfox` matches `Firefox`.  A search word can also match the start
+/// of a longer word, so results can be found while typing.  This is used
+/// as a fallback when the configured matchers find too few items since
+/// it accepts much worse matches.
+///
+/// `search` must already be folded and the indices of the returned match
+/// are into the original text.
+pub fn typo_match(text: &SearchText, search: &str) -> Option<Match> {
+    let search_words = search_words(search);
+    if search_words.is_empty() {
+        return None;
+    }
+    // Each typo can account for at most one character of a search word
+    // that's missing from the text, which rules out most texts cheaply.
+    let too_many_missing = search_words.iter().any(|word| {
+        let missing =
+            word.iter().filter(|&&c| !text.folded.contains(c)).count();
+        missing > max_typos(word.len())
+    });
+    if too_many_missing {
+        return None;
+    }
+    let text_words = text.words();
+    let mut used = vec![false; text_words.len()];
+    let mut indices = Vec::new();
+    let mut typos = 0;
+    let mut search_len = 0;
+    for search_word in &search_words {
+        let allowed = max_typos(search_word.len());
+        let (idx, distance) = text_words
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !used[*idx])
+            .map(|(idx, word)| {
+                let prefix_len = word.chars.len().min(search_word.len());
+                let distance = edit_distance(search_word, &word.chars)
+                    .min(edit_distance(search_word, &word.chars[..prefix_len]));
+                (idx, distance)
+            })
+            .min_by_key(|(_, distance)| *distance)?;
+        if distance > allowed {
+            return None;
+        }
+        used[idx] = true;
+        typos += distance;
+        search_len += search_word.len();
+        let word = &text_words[idx];
+        indices.extend(word.start..word.start + word.chars.len());
+    }
+    indices.sort_unstable();
+    let score = 1.0 - typos as f64 / search_len as f64;
+    Some(Match::new(score, text.original_indices(&indices)))
+}
+
+/// Skim fuzzy matching.
+#[derive(Debug)]
+pub struct Fuzzy;
+
+impl Matcher for Fuzzy {
+    fn matches(&self, text: &SearchText, search: &str) -> Option<Match> {
+        let text = &text.folded;
+        if !is_subsequence(text, search) {
+            return None;
+        }
+        match fuzzy_indices(text, search) {
+            Some((score, indices)) if score > 0 => {
+                let max = MAX_SKIM_CHAR_SCORE * search.chars().count() as f64;
+                Some(Match::new((score as f64 / max).min(1.0), indices))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Fuzzy matching of file paths relative to a directory.
+///
+/// Matches in the file name count the most and matches at the start of a
+/// path segment count more than matches inside one, so `proj/readme`
+/// ranks `proj/README.md` above `old/projects/docs/readme.md` and `readme`
+/// ranks `docs/readme.md` above `readme-files/notes.md`.
+#[derive(Debug)]
+pub struct PathFuzzy;
+
+impl Matcher for PathFuzzy {
+    fn matches(&self, text: &SearchText, search: &str) -> Option<Match> {
+        let m = Fuzzy.matches(text, search)?;
+        let chars: Vec<char> = text.folded.chars().collect();
+        let name_start = chars
+            .iter()
+            .rposition(|&c| c == '/')
+            .map_or(0, |slash| slash + 1);
+        let matched: Vec<usize> = m
+            .indices
+            .iter()
+            .copied()
+            .filter(|&i| chars[i] != '/')
+            .collect();
+        if matched.is_empty() {
+            return Some(m);
+        }
+        // The index of the path segment each character is in
+        let mut segments = 0;
+        let segment_of: Vec<usize> = chars
+            .iter()
+            .map(|&c| {
+                let segment = segments;
+                if c == '/' {
+                    segments += 1;
+                }
+                segment
+            })
+            .collect();
+        let in_name = matched.iter().filter(|&&i| i >= name_start).count();
+        let at_segment_start = matched
+            .iter()
+            .filter(|&&i| i == 0 || chars[i - 1] == '/')
+            .count();
+        let search_segments =
+            search.split('/').filter(|part| !part.is_empty()).count();
+        let mut touched: Vec<usize> =
+            matched.iter().map(|&i| segment_of[i]).collect();
+        touched.dedup();
+
+        let in_name = in_name as f64 / matched.len() as f64;
+        let at_segment_start = at_segment_start.min(search_segments) as f64
+            / search_segments.max(1) as f64;
+        let touched = touched.len() as f64 / (segments + 1) as f64;
+        let score = 0.35 * m.score
+            + 0.35 * in_name
+            + 0.2 * at_segment_start
+            + 0.1 * touched;
+        Some(Match::new(score, m.indices))
+    }
+}
+
+/// Substring matching.
+///
+/// Matches covering more of the text and matches at the start of a word
+/// score higher.
+#[derive(Debug)]
+pub struct Substring;
+
+impl Matcher for Substring {
+    fn matches(&self, text: &SearchText, search: &str) -> Option<Match> {
+        let text: Vec<char> = text.folded.chars().collect();
+        let search: Vec<char> = search.chars().collect();
+        if search.is_empty() || search.len() > text.len() {
+            return None;
+        }
+        let pos = (0..=text.len() - search.len()).find(|&pos| {
+            text[pos..].iter().zip(&search).all(|(t, s)| t == s)
+        })?;
+        let indices = (pos..pos + search.len()).collect();
+        if search.len() == text.len() {
+            return Some(Match::new(1.0, indices));
+        }
+        let at_word_start = pos == 0 || !text[pos - 1].is_alphanumeric();
+        let coverage = search.len() as f64 / text.len() as f64;
+        let score =
+            0.5 + 0.3 * coverage + if at_word_start { 0.2 } else { 0.0 };
+        Some(Match::new(score, indices))
+    }
+}
+
+/// Matches when each word of the search string is the start of a word
+/// of the text, in order.
+///
+/// `stu co` matches `Visual Studio Code`.
+#[derive(Debug)]
+pub struct WordPrefix;
+
+impl Matcher for WordPrefix {
+    fn matches(&self, text: &SearchText, search: &str) -> Option<Match> {
+        let text_words = text.words();
+        let search_words = search_words(search);
+        if search_words.is_empty() {
+            return None;
+        }
+        let mut remaining = text_words.iter().enumerate();
+        let mut indices = Vec::new();
+        let mut matched_len = 0;
+        let mut first_word = false;
+        for search_word in &search_words {
+            let (idx, word) = remaining
+                .find(|(_, word)| word.chars.starts_with(search_word))?;
+            if idx == 0 {
+                first_word = true;
+            }
+            matched_len += word.chars.len();
+            indices.extend(word.start..word.start + search_word.len());
+        }
+        let coverage = indices.len() as f64 / matched_len as f64;
+        let score = 0.5 + 0.4 * coverage + if first_word { 0.1 } else { 0.0 };
+        Some(Match::new(score, indices))
+    }
+}
+
+/// Matches the first letters of the text's words.
+///
+/// `vsc` matches `Visual Studio Code` and `lo w` matches
+/// `LibreOffice Writer`.  Whitespace in the search string is ignored.
+#[derive(Debug)]
+pub struct Initials;
+
+impl Matcher for Initials {
+    fn matches(&self, text: &SearchText, search: &str) -> Option<Match> {
+        let text_words = text.words();
+        let search: Vec<char> =
+            search.chars().filter(|c| !c.is_whitespace()).collect();
+        // The initial of a one word name is only its first letter
+        if search.is_empty() || text_words.len() < 2 {
+            return None;
+        }
+        let mut remaining = text_words.iter().enumerate();
+        let mut indices = Vec::new();
+        let mut skipped = false;
+        for &s in &search {
+            let (idx, word) =
+                remaining.find(|(_, word)| word.chars.first() == Some(&s))?;
+            if idx != indices.len() {
+                skipped = true;
+            }
+            indices.push(word.start);
+        }
+        let coverage = indices.len() as f64 / text_words.len() as f64;
+        let score = if indices.len() == text_words.len() {
+            ALL_INITIALS_SCORE
+        } else if !skipped {
+            0.6 + 0.2 * coverage
+        } else {
+            0.3 + 0.3 * coverage
+        };
+        Some(Match::new(score, indices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalize::fold;
+
+    fn matches(
+        matcher: &dyn Matcher,
+        text: &str,
+        search: &str,
+    ) -> Option<Match> {
+        matcher.matches(&SearchText::new(text), &fold(search))
+    }
+
+    fn score(matcher: &dyn Matcher, text: &str, search: &str) -> Option<f64> {
+        matches(matcher, text, search).map(|m| m.score)
+    }
+
+    #[test]
+    fn fuzzy_is_normalized() {
+        let full = score(&Fuzzy, "Firefox", "Firefox").unwrap();
+        assert!(full > 0.0 && full <= 1.0);
+        assert!(score(&Fuzzy, "Firefox", "fox").unwrap() < full);
+        assert_eq!(score(&Fuzzy, "Firefox", "xf"), None);
+        assert_eq!(
+            matches(&Fuzzy, "Firefox", "ffx").unwrap().indices,
+            vec![0, 4, 6]
+        );
+    }
+
+    #[test]
+    fn path_fuzzy() {
+        let score = |path, search| score(&PathFuzzy, path, search).unwrap();
+        assert!(
+            score("proj/README.md", "proj/readme")
+                > score("old/projects/docs/readme.md", "proj/readme")
+        );
+        assert!(
+            score("docs/readme.md", "readme")
+                > score("readme-files/notes.md", "readme")
+        );
+        assert!(score("docs", "docs") > score("docs/report.pdf", "docs"));
+        assert!(score("docs/readme.md", "docs/readme.md") <= 1.0);
+        assert_eq!(
+            matches(&PathFuzzy, "proj/README.md", "p/read")
+                .unwrap()
+                .indices,
+            vec![0, 4, 5, 6, 7, 8]
+        );
+        assert!(matches(&PathFuzzy, "proj/README.md", "readme/proj").is_none());
+    }
+
+    #[test]
+    fn substring() {
+        assert_eq!(score(&Substring, "Firefox", "firefox"), Some(1.0));
+        let word_start = score(&Substring, "Mozilla Firefox", "fire").unwrap();
+        let inner = score(&Substring, "Campfire Stories", "fire").unwrap();
+        assert!(word_start > inner);
+        assert_eq!(score(&Substring, "Firefox", "frx"), None);
+        assert_eq!(
+            matches(&Substring, "Campfire", "FIRE").unwrap().indices,
+            vec![4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn word_prefix() {
+        let m = matches(&WordPrefix, "Visual Studio Code", "stu co").unwrap();
+        assert_eq!(m.indices, vec![7, 8, 9, 14, 15]);
+        assert!(
+            score(&WordPrefix, "Visual Studio Code", "vis").unwrap()
+                > score(&WordPrefix, "Visual Studio Code", "stu").unwrap()
+        );
+        assert_eq!(score(&WordPrefix, "Visual Studio Code", "co stu"), None);
+        assert_eq!(score(&WordPrefix, "Visual Studio Code", "isu"), None);
+    }
+
+    #[test]
+    fn initials() {
+        assert_eq!(
+            score(&Initials, "Visual Studio Code", "vsc"),
+            Some(ALL_INITIALS_SCORE)
+        );
+        assert_eq!(score(&Initials, "Firefox", "f"), None);
+        let m = matches(&Initials, "LibreOffice Writer", "lo w").unwrap();
+        assert_eq!(m.score, ALL_INITIALS_SCORE);
+        assert_eq!(m.indices, vec![0, 5, 12]);
+        let prefix = score(&Initials, "LibreOffice Writer", "lo").unwrap();
+        let skipped = score(&Initials, "LibreOffice Writer", "lw").unwrap();
+        assert!(ALL_INITIALS_SCORE > prefix && prefix > skipped);
+        assert_eq!(score(&Initials, "LibreOffice Writer", "wl"), None);
+    }
+
+    #[test]
+    fn best_of_matchers() {
+        let matchers = vec![
+            MatcherKind::Fuzzy.matcher(),
+            MatcherKind::Initials.matcher(),
+        ];
+        let m = best_match(
+            &matchers,
+            &SearchText::new("Visual Studio Code"),
+            "vsc",
+        )
+        .unwrap();
+        assert_eq!(m.score, ALL_INITIALS_SCORE);
+        // Typing a name beats typing its initials
+        assert!(
+            score(&Substring, "Visual Studio Code", "visual studio code")
+                .unwrap()
+                > ALL_INITIALS_SCORE
+        );
+        assert!(best_match(
+            &matchers,
+            &SearchText::new("Visual Studio Code"),
+            "zz"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn ignores_case_and_diacritics() {
+        assert_eq!(score(&Substring, "Café", "CAFE"), Some(1.0));
+        assert_eq!(
+            score(&Initials, "Éditeur Über", "eu"),
+            Some(ALL_INITIALS_SCORE)
+        );
+        let m = best_match(
+            &default_matchers(),
+            &SearchText::new("Straße Karte"),
+            &fold("strasse"),
+        )
+        .unwrap();
+        assert_eq!(m.indices, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn typos() {
+        let text = SearchText::new("Mozilla Firefox");
+        let m = typo_match(&text, "fierfox").unwrap();
+        assert_eq!(m.indices, vec![8, 9, 10, 11, 12, 13, 14]);
+        assert!(m.score > 0.8);
+        let text = SearchText::new("Thunderbird");
+        assert!(typo_match(&text, "thunderbrid").is_some());
+        assert!(typo_match(&text, "thundr").is_some());
+        assert!(typo_match(&text, "tnuhderbrid").is_none());
+        assert!(typo_match(&SearchText::new("Gimp"), "gmi").is_some());
+        assert!(typo_match(&SearchText::new("Gimp"), "xy").is_none());
+        assert_eq!(edit_distance(&['a', 'b'], &['b', 'a']), 1);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(index_ranges(&[0, 1, 2, 5, 7, 8]), vec![0..3, 5..6, 7..9]);
+        assert!(index_ranges(&[]).is_empty());
+    }
+}
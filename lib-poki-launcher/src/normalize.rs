@@ -0,0 +1,183 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use caseless::default_case_fold_str;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization as _;
+
+/// Fold a character into its search form.
+///
+/// The character is case folded, decomposed (NFKD) and stripped of
+/// diacritics, so it can become zero or more characters.
+fn fold_char(c: char, out: &mut String) {
+    if c.is_ascii() {
+        out.push(c.to_ascii_lowercase());
+        return;
+    }
+    let mut buf = [0; 4];
+    out.extend(
+        default_case_fold_str(c.encode_utf8(&mut buf))
+            .nfkd()
+            .filter(|c| !is_combining_mark(*c)),
+    );
+}
+
+/// Convert text into the form used for matching.
+///
+/// Search strings are folded with this before being matched against
+/// the search form of the items.
+pub fn fold(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_ascii_lowercase();
+    }
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        fold_char(c, &mut folded);
+    }
+    folded
+}
+
+/// A word of a text.
+#[derive(Debug, PartialEq)]
+pub struct Word {
+    /// Index of the first character of the word in the text.
+    pub start: usize,
+    /// The characters of the word.
+    pub chars: Vec<char>,
+}
+
+/// The text of an item prepared for matching.
+#[derive(Debug, Clone)]
+pub struct SearchText {
+    /// The folded text that searches are matched against.
+    pub folded: String,
+    /// Index of the original character each folded character came from.
+    ///
+    /// `None` if every character folded into exactly one character.
+    char_map: Option<Vec<usize>>,
+    /// Indices of the folded characters that start a word.
+    ///
+    /// Word boundaries are found in the original text since folding
+    /// loses the case needed to split camelCase words.
+    word_starts: Vec<usize>,
+}
+
+impl SearchText {
+    pub fn new(text: &str) -> Self {
+        let mut folded = String::with_capacity(text.len());
+        let mut char_map = Vec::with_capacity(text.len());
+        let mut word_starts = Vec::new();
+        let mut prev: Option<char> = None;
+        for (idx, c) in text.chars().enumerate() {
+            let starts_word = c.is_alphanumeric()
+                && match prev {
+                    Some(prev) => {
+                        !prev.is_alphanumeric()
+                            || (prev.is_lowercase() && c.is_uppercase())
+                    }
+                    None => true,
+                };
+            let start = char_map.len();
+            let len = folded.len();
+            fold_char(c, &mut folded);
+            char_map.extend(folded[len..].chars().map(|_| idx));
+            if starts_word && char_map.len() > start {
+                word_starts.push(start);
+            }
+            prev = Some(c);
+        }
+        let identity = char_map.iter().enumerate().all(|(i, idx)| i == *idx);
+        SearchText {
+            folded,
+            char_map: if identity { None } else { Some(char_map) },
+            word_starts,
+        }
+    }
+
+    /// Split the folded text into words.
+    pub fn words(&self) -> Vec<Word> {
+        let chars: Vec<char> = self.folded.chars().collect();
+        self.word_starts
+            .iter()
+            .enumerate()
+            .map(|(n, &start)| {
+                let limit =
+                    self.word_starts.get(n + 1).copied().unwrap_or(chars.len());
+                let end = (start..limit)
+                    .find(|&i| !chars[i].is_alphanumeric())
+                    .unwrap_or(limit);
+                Word {
+                    start,
+                    chars: chars[start..end].to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    /// Convert indices into the folded text to indices into the original.
+    pub fn original_indices(&self, indices: &[usize]) -> Vec<usize> {
+        match &self.char_map {
+            None => indices.to_vec(),
+            Some(char_map) => {
+                let mut original: Vec<usize> =
+                    indices.iter().map(|&i| char_map[i]).collect();
+                original.dedup();
+                original
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_diacritics_and_case() {
+        assert_eq!(fold("Café"), "cafe");
+        assert_eq!(fold("ÉCOLE Straße"), "ecole strasse");
+        assert_eq!(fold("Ελληνικά"), "ελληνικα");
+        assert_eq!(fold("ﬁle"), "file");
+    }
+
+    #[test]
+    fn map_back_to_original() {
+        let text = SearchText::new("Straße Café");
+        assert_eq!(text.folded, "strasse cafe");
+        // `ss` both came from `ß`
+        assert_eq!(text.original_indices(&[4, 5, 11]), vec![4, 10]);
+
+        let ascii = SearchText::new("Firefox");
+        assert_eq!(ascii.original_indices(&[0, 4]), vec![0, 4]);
+    }
+
+    #[test]
+    fn words_from_original_case() {
+        let words: Vec<(usize, String)> = SearchText::new("LibreOffice Écrit")
+            .words()
+            .into_iter()
+            .map(|word| (word.start, word.chars.into_iter().collect()))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                (0, "libre".to_owned()),
+                (5, "office".to_owned()),
+                (12, "ecrit".to_owned()),
+            ]
+        );
+    }
+}
@@ -81,7 +81,7 @@ impl App {
     /// let entries = vec![Path::new("./firefox.desktop"), Path::new("./chrome.desktop")];
     /// let (apps, errors): (Vec<_>, Vec<_>) = entries
     ///     .into_iter()
-    ///     .map(|path| App::parse_desktop_file(&path))
+    ///     .map(|path| App::parse_desktop_file(path))
     ///     .partition(Result::is_ok);
     /// let mut apps: Vec<_> = apps
     ///     .into_iter()
@@ -92,7 +92,7 @@ impl App {
     pub fn parse_desktop_file(path: impl AsRef<Path>) -> Result<Option<Self>> {
         let path_str = path.as_ref().display().to_string();
         // TODO Finish implementation
-        let mut file = File::open(path).with_context(|| {
+        let mut file = File::open(path.as_ref()).with_context(|| {
             format!("Error opening desktop file {}", path_str)
         })?;
         let mut buf = Vec::new();
@@ -123,6 +123,7 @@ impl App {
         let mut hidden = None;
         let mut icon = None;
         let mut terminal = None;
+        let mut comment = None;
 
         for attr in section.attrs {
             match attr.name {
@@ -132,6 +133,7 @@ impl App {
                 b"Hidden" => hidden = Some(from_utf8(attr.value)?),
                 b"Icon" => icon = Some(from_utf8(attr.value)?),
                 b"Terminal" => terminal = Some(from_utf8(attr.value)?),
+                b"Comment" => comment = Some(from_utf8(attr.value)?),
                 _ => {}
             }
         }
@@ -158,7 +160,6 @@ impl App {
         let exec = exec.ok_or(EntryParseError::MissingExec {
             file: path_str.to_owned(),
         })?;
-        let exec = strip_entry_args(exec);
         let icon = match icon {
             Some(icon) => icon.to_owned(),
             None => String::new(),
@@ -175,7 +176,12 @@ impl App {
             }
         };
 
-        Ok(Some(App::new(name.to_owned(), icon, exec, terminal)))
+        let mut app =
+            App::new(name.to_owned(), icon, strip_entry_args(exec), terminal);
+        app.raw_exec = exec.to_owned();
+        app.path = path.as_ref().to_owned();
+        app.comment = comment.unwrap_or_default().to_owned();
+        Ok(Some(app))
     }
 }
 
@@ -196,7 +202,7 @@ mod test {
         fn has_args() {
             let exec = "/usr/bin/cat %f --flag";
             let exec_no_args = "/usr/bin/cat --flag".to_owned();
-            assert_eq!(strip_entry_args(&exec), exec_no_args);
+            assert_eq!(strip_entry_args(exec), exec_no_args);
         }
     }
 
@@ -209,7 +215,7 @@ mod test {
             use std::path::Path;
 
             let path = Path::new("./test.desktop");
-            let mut file = File::create(&path).unwrap();
+            let mut file = File::create(path).unwrap();
             file.write_all(
                 b"[Desktop Entry]
  Name=Test
@@ -217,7 +223,7 @@ mod test {
  Exec=/usr/bin/test --with-flag %f",
             )
             .unwrap();
-            let app = App::parse_desktop_file(&path).unwrap().unwrap();
+            let app = App::parse_desktop_file(path).unwrap().unwrap();
             let other_app = App::new(
                 "Test".to_owned(),
                 "testicon".to_owned(),
@@ -226,7 +232,8 @@ mod test {
             );
             // Note, apps will have different uuids but Eq doesn't consider them
             assert_eq!(app, other_app);
-            remove_file(&path).unwrap();
+            assert_eq!(app.raw_exec, "/usr/bin/test --with-flag %f");
+            remove_file(path).unwrap();
         }
     }
 }
@@ -0,0 +1,215 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::App;
+use crate::HOME_PATH;
+use anyhow::{anyhow, Context as _, Result};
+use lazy_static::lazy_static;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+lazy_static! {
+    /// MIME types by file extension, read from shared-mime-info.
+    static ref EXTENSION_TYPES: HashMap<String, String> = {
+        let mut types = HashMap::new();
+        for dir in data_dirs() {
+            let path = dir.join("mime/globs2");
+            if let Ok(text) = fs::read_to_string(path) {
+                for (ext, mime_type) in parse_globs2(&text) {
+                    types.entry(ext).or_insert(mime_type);
+                }
+            }
+        }
+        types
+    };
+}
+
+/// The XDG data directories, most important first.
+fn data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| HOME_PATH.join(".local/share"));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+    iter::once(data_home)
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .collect()
+}
+
+/// Directories desktop files are installed in, most important first.
+fn application_dirs() -> Vec<PathBuf> {
+    data_dirs()
+        .into_iter()
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+/// Get the extension globs of a shared-mime-info `globs2` file and their
+/// MIME types, highest weight first.
+///
+/// Lines are `weight:type:glob`, other globs than `*.ext` are skipped.
+fn parse_globs2(text: &str) -> Vec<(String, String)> {
+    let mut globs: Vec<(u32, String, String)> = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.split(':');
+            let weight = parts.next()?.parse().ok()?;
+            let mime_type = parts.next()?;
+            let ext = parts.next()?.strip_prefix("*.")?;
+            if ext.contains(&['*', '?', '['][..]) {
+                return None;
+            }
+            Some((weight, ext.to_lowercase(), mime_type.to_owned()))
+        })
+        .collect();
+    // Stable so types of the same weight keep the file's order
+    globs.sort_by_key(|(weight, _, _)| Reverse(*weight));
+    globs
+        .into_iter()
+        .map(|(_, ext, mime_type)| (ext, mime_type))
+        .collect()
+}
+
+/// Guess the MIME type of a file from its name, without reading it.
+///
+/// The longest known extension wins, so `a.tar.gz` is a compressed tar
+/// archive rather than a gzip file.
+pub fn guess_mime_type(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    name.match_indices('.')
+        .filter_map(|(idx, _)| EXTENSION_TYPES.get(&name[idx + 1..]))
+        .map(String::as_str)
+        .next()
+}
+
+/// Run `xdg-mime query` with some arguments, returning its output.
+fn xdg_mime_query(args: &[&std::ffi::OsStr]) -> Result<String> {
+    let output = Command::new("xdg-mime")
+        .arg("query")
+        .args(args)
+        .output()
+        .context("Error running xdg-mime")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "xdg-mime failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Get the MIME type of a file.
+pub fn file_mime_type(path: &Path) -> Result<String> {
+    xdg_mime_query(&["filetype".as_ref(), path.as_os_str()]).with_context(
+        || format!("Error getting the type of {}", path.display()),
+    )
+}
+
+/// Get the ids of the desktop files a `mimeinfo.cache` file lists for a
+/// MIME type.
+fn parse_mimeinfo_cache<'a>(
+    text: &'a str,
+    mime_type: &str,
+) -> impl Iterator<Item = &'a str> {
+    let mime_type = mime_type.to_owned();
+    text.lines()
+        .filter_map(move |line| {
+            let mut parts = line.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(ids)) if key == mime_type => Some(ids),
+                _ => None,
+            }
+        })
+        .flat_map(|ids| ids.split(';'))
+        .filter(|id| !id.is_empty())
+}
+
+/// Find and parse a desktop file by its id.
+fn find_app(dirs: &[PathBuf], id: &str) -> Option<App> {
+    dirs.iter()
+        .map(|dir| dir.join(id))
+        .find(|path| path.exists())
+        .and_then(|path| App::parse_desktop_file(path).ok().flatten())
+}
+
+/// Get the apps that can open files of a MIME type.
+pub fn apps_for_mime_type(mime_type: &str) -> Vec<App> {
+    let dirs = application_dirs();
+    let mut ids: Vec<String> = Vec::new();
+    for dir in &dirs {
+        if let Ok(text) = fs::read_to_string(dir.join("mimeinfo.cache")) {
+            for id in parse_mimeinfo_cache(&text, mime_type) {
+                if !ids.iter().any(|other| other == id) {
+                    ids.push(id.to_owned());
+                }
+            }
+        }
+    }
+    let mut apps: Vec<App> =
+        ids.iter().filter_map(|id| find_app(&dirs, id)).collect();
+    apps.dedup_by(|a, b| a.name == b.name);
+    apps
+}
+
+/// Get the app that opens files of a MIME type by default.
+pub fn default_app(mime_type: &str) -> Option<App> {
+    let id = xdg_mime_query(&["default".as_ref(), mime_type.as_ref()]).ok()?;
+    find_app(&application_dirs(), &id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mimeinfo_cache() {
+        let cache = "[MIME Cache]\n\
+                     text/plain=gedit.desktop;vim.desktop;\n\
+                     text/plain-extra=other.desktop;\n\
+                     image/png=gimp.desktop;\n";
+        let ids: Vec<_> = parse_mimeinfo_cache(cache, "text/plain").collect();
+        assert_eq!(ids, vec!["gedit.desktop", "vim.desktop"]);
+        assert_eq!(parse_mimeinfo_cache(cache, "video/mp4").count(), 0);
+    }
+
+    #[test]
+    fn globs2() {
+        let globs = "# comment\n\
+                     50:application/gzip:*.gz\n\
+                     50:text/x-readme:README*\n\
+                     80:application/x-compressed-tar:*.tar.gz\n\
+                     50:image/jpeg:*.JPG:cs\n";
+        assert_eq!(
+            parse_globs2(globs),
+            vec![
+                (
+                    "tar.gz".to_owned(),
+                    "application/x-compressed-tar".to_owned()
+                ),
+                ("gz".to_owned(), "application/gzip".to_owned()),
+                ("jpg".to_owned(), "image/jpeg".to_owned()),
+            ]
+        );
+    }
+}
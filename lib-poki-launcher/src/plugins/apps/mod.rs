@@ -16,99 +16,287 @@
  */
 /// Parse desktop entries
 pub mod desktop_entry;
+/// Find the apps that open a type of file
+pub mod mime;
 /// Run an app
 pub mod runner;
 /// Scan for desktop entries
 pub mod scan;
+/// Transliterate app names into ASCII
+pub mod transliterate;
 
 use super::ListItem;
-use super::Plugin;
+use super::{
+    frecent_items, ranked_items, scored_items, FrecentItem, Plugin,
+    PluginOptions, ScoredItem,
+};
+use crate::cancel::CancelToken;
 use crate::config::Config;
 use crate::event::Event;
 use crate::frecency_db::*;
+use crate::matcher::index_ranges;
+use crate::run::xdg_open;
+use crate::{Action, ActionResult, Badge};
 use anyhow::{anyhow, Error, Result};
 use log::{debug, error, warn};
-use notify::{watcher, RecursiveMode, Watcher};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use scan::{is_desktop_file, path_desktop_entries, DesktopFiles};
 use serde_derive::{Deserialize, Serialize};
-use serde_json::{Map, Value};
 use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
+use std::collections::HashMap;
 use std::default::Default;
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Sender};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
 pub struct Apps {
+    index: Arc<AppIndex>,
+    term_cmd: Option<String>,
+}
+
+/// The apps and the desktop files they were read from.
+///
+/// Shared with the desktop file watcher, which keeps it up to date.
+struct AppIndex {
     db: Mutex<AppsDB>,
+    files: Mutex<DesktopFiles>,
+    /// Where `files` is saved between runs.
+    cache_path: PathBuf,
     app_paths: Vec<String>,
-    term_cmd: Option<String>,
+    transliterate: bool,
 }
 
-impl Apps {
-    pub fn init(config: &Config) -> Result<(Self, Vec<Error>)> {
-        let db_path = config.data_dir.join("apps.db");
-        let app_paths = match get_app_paths(&config.file_options.plugins) {
-            Ok(app_paths) => app_paths,
-            Err(_) => vec![
+/// Options of the apps plugin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AppsOptions {
+    /// Directories to search for desktop files.
+    pub app_paths: Vec<String>,
+    /// Command used to run terminal apps.
+    pub term_cmd: Option<String>,
+    /// Also match app names by their pinyin or romaji.
+    pub transliterate: bool,
+}
+
+impl Default for AppsOptions {
+    fn default() -> Self {
+        AppsOptions {
+            app_paths: vec![
                 "/usr/share/applications".into(),
                 "~/.local/share/applications/".into(),
                 "/var/lib/snapd/desktop/applications".into(),
                 "/var/lib/flatpak/exports/share/applications".into(),
             ],
-        };
+            term_cmd: None,
+            transliterate: false,
+        }
+    }
+}
+
+impl Apps {
+    pub fn init(
+        config: &Config,
+        options: &PluginOptions<AppsOptions>,
+    ) -> Result<(Self, Vec<Error>)> {
+        let db_path = config.data_dir.join("apps.db");
+        let cache_path = config.data_dir.join("desktop_files.cache");
+        let mut errors = Vec::new();
+        let app_paths: Vec<String> = options
+            .plugin
+            .app_paths
+            .iter()
+            .filter(|path| match shellexpand::full(path) {
+                Ok(_) => true,
+                Err(e) => {
+                    errors.push(Error::new(e).context(format!(
+                        "Invalid path `{}` in the `app_paths` option of \
+                         plugin `apps`",
+                        path
+                    )));
+                    false
+                }
+            })
+            .cloned()
+            .collect();
         if app_paths.is_empty() {
             warn!("The list of search paths for apps is empty so none will be found");
         }
-        let term_cmd = get_term_cmd(&config.file_options.plugins).ok();
-        let (db, errors) = AppsDB::from_desktop_entries(&db_path, &app_paths)?;
+        let mut db = AppsDB::new(&db_path)?;
+        db.set_matchers(options.matchers());
+        if let Some(typo_fallback) = options.typo_fallback {
+            db.set_typo_fallback(typo_fallback);
+        }
+        let index = AppIndex {
+            db: Mutex::new(db),
+            files: Mutex::new(DesktopFiles::load(&cache_path)),
+            cache_path,
+            app_paths,
+            transliterate: options.plugin.transliterate,
+        };
+        errors.extend(index.rescan(&config.file_options.aliases)?);
 
         Ok((
             Apps {
-                db: Mutex::new(db),
-                app_paths,
-                term_cmd,
+                index: Arc::new(index),
+                term_cmd: options.plugin.term_cmd.clone(),
             },
             errors,
         ))
     }
 }
 
-fn get_apps_config(plugins: &Value) -> Result<&Map<String, Value>> {
-    Ok(plugins
-        .as_object()
-        .ok_or(anyhow!(""))?
-        .get("apps")
-        .ok_or(anyhow!(""))?
-        .as_object()
-        .ok_or(anyhow!(""))?)
-}
+impl AppIndex {
+    /// Scan all the desktop files, only parsing the ones that changed, and
+    /// swap in their apps.
+    fn rescan(&self, aliases: &HashMap<String, String>) -> Result<Vec<Error>> {
+        let (apps, errors) = {
+            let mut files = self.files.lock().expect("Files Mutex poisoned");
+            let mut errors = files.scan(&self.app_paths);
+            if let Err(e) = files.save(&self.cache_path) {
+                errors.push(e);
+            }
+            (files.apps(), errors)
+        };
+        let new = NewEntries::new(apps, alternate_names(self.transliterate))?;
+        let mut db = self.db.lock().expect("Apps Mutex poisoned");
+        db.swap_entries(new)?;
+        db.set_config_aliases(aliases)?;
+        Ok(errors)
+    }
 
-fn get_app_paths(plugins: &Value) -> Result<Vec<String>> {
-    Ok(get_apps_config(plugins)?
-        .get("app_paths")
-        .ok_or(anyhow!(""))?
-        .as_array()
-        .ok_or(anyhow!(""))?
-        .into_iter()
-        .filter_map(|item| item.as_str().map(|s| s.to_owned()))
-        .collect())
+    /// Apply a change the watcher saw to the desktop files.
+    ///
+    /// Only the changed files are read again, unless the watcher missed
+    /// changes and everything has to be scanned again.  Returns whether
+    /// the apps may have changed.
+    fn apply(
+        &self,
+        event: DebouncedEvent,
+        aliases: &HashMap<String, String>,
+    ) -> Result<bool> {
+        let changed = match event {
+            DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => {
+                self.update_path(&path)?
+            }
+            DebouncedEvent::Remove(path) => self.remove_path(&path),
+            DebouncedEvent::Rename(from, to) => {
+                let removed = self.remove_path(&from);
+                self.update_path(&to)? || removed
+            }
+            DebouncedEvent::Rescan => {
+                for e in self.rescan(aliases)? {
+                    warn!("{:?}", e);
+                }
+                return Ok(true);
+            }
+            DebouncedEvent::Error(e, path) => {
+                return Err(Error::new(e).context(format!(
+                    "Error watching desktop files {:?}",
+                    path
+                )))
+            }
+            _ => false,
+        };
+        if changed {
+            let files = self.files.lock().expect("Files Mutex poisoned");
+            if let Err(e) = files.save(&self.cache_path) {
+                warn!("{:?}", e);
+            }
+            let mut db = self.db.lock().expect("Apps Mutex poisoned");
+            db.set_config_aliases(aliases)?;
+        }
+        Ok(changed)
+    }
+
+    /// Read a created or modified desktop file and update its app.
+    fn update_file(&self, path: &Path) -> Result<bool> {
+        if !is_desktop_file(path) {
+            return Ok(false);
+        }
+        let mut files = self.files.lock().expect("Files Mutex poisoned");
+        let old = files.update(path)?;
+        let mut db = self.db.lock().expect("Apps Mutex poisoned");
+        if let Some(old) = old {
+            Self::forget_app(&files, &mut db, &old)?;
+        }
+        if let Some(app) = files.app(path) {
+            debug!("Updating app {} from {}", app.name, path.display());
+            db.upsert_entry(app.clone())?;
+        }
+        Ok(true)
+    }
+
+    /// Read the desktop files at or under a created or modified path, like
+    /// a directory of desktop files moved in, and update their apps.
+    fn update_path(&self, path: &Path) -> Result<bool> {
+        if !path.is_dir() {
+            return self.update_file(path);
+        }
+        let (found, errors) = path_desktop_entries(path);
+        for e in errors {
+            warn!("{:?}", e);
+        }
+        let mut changed = false;
+        for file in found {
+            match self.update_file(&file) {
+                Ok(updated) => changed |= updated,
+                Err(e) => warn!("{:?}", e),
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Remove the apps of the desktop files at or under a removed path.
+    fn remove_path(&self, path: &Path) -> bool {
+        let mut files = self.files.lock().expect("Files Mutex poisoned");
+        let removed = files.remove(path);
+        if removed.is_empty() {
+            return false;
+        }
+        let mut db = self.db.lock().expect("Apps Mutex poisoned");
+        for old in &removed {
+            debug!("Removing app {} of {}", old.name, path.display());
+            if let Err(e) = Self::forget_app(&files, &mut db, old) {
+                warn!("{:?}", e);
+            }
+        }
+        true
+    }
+
+    /// Remove an app a desktop file no longer has, unless another desktop
+    /// file has the same app.
+    fn forget_app(
+        files: &DesktopFiles,
+        db: &mut AppsDB,
+        app: &App,
+    ) -> Result<()> {
+        match files.find(app) {
+            Some(other) => {
+                db.upsert_entry(other.clone())?;
+                Ok(())
+            }
+            None => db.remove_entry(app),
+        }
+    }
 }
 
-fn get_term_cmd(plugins: &Value) -> Result<String> {
-    Ok(get_apps_config(plugins)?
-        .get("term_cmd")
-        .ok_or(anyhow!(""))?
-        .as_str()
-        .ok_or(anyhow!(""))?
-        .to_owned())
+/// Get the function giving the transliterations of app names if
+/// transliteration is turned on.
+fn alternate_names(transliterate: bool) -> Option<fn(&App) -> Vec<String>> {
+    if transliterate {
+        Some(transliterate::alternate_names)
+    } else {
+        None
+    }
 }
 
 impl Plugin for Apps {
-    fn matcher(&self, _: &Config, _: &str) -> bool {
-        true
+    fn name(&self) -> &str {
+        "apps"
     }
 
     fn search(
@@ -116,38 +304,123 @@ impl Plugin for Apps {
         _config: &Config,
         input: &str,
         num_items: usize,
+        cancel: &CancelToken,
     ) -> Result<Vec<ListItem>> {
-        let db = self.db.lock().expect("Apps Mutex poisoned");
-        Ok(db
-            .get_ranked_list(input, Some(num_items))?
-            .into_iter()
-            .map(ListItem::from)
-            .collect())
+        let db = self.index.db.lock().expect("Apps Mutex poisoned");
+        ranked_items(&*db, input, num_items, cancel)
     }
 
-    fn run(&mut self, _: &Config, id: u64) -> Result<()> {
+    fn blended_search(
+        &self,
+        _: &Config,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ScoredItem>> {
+        let db = self.index.db.lock().expect("Apps Mutex poisoned");
+        scored_items(&*db, input, num_items, cancel)
+    }
+
+    fn run(&self, _: &Config, id: u64) -> Result<()> {
         let cont = self
+            .index
             .db
             .lock()
             .expect("Apps Mutex poisoned")
             .get_by_id(id)?
-            .unwrap();
+            .ok_or_else(|| anyhow!("No app with id {}", id))?;
         cont.item.run(&self.term_cmd)?;
         Ok(())
     }
 
-    fn reload(&mut self, _: &Config) -> Result<Vec<Error>> {
-        let errors = self
+    fn top_items(
+        &self,
+        _: &Config,
+        num_items: usize,
+    ) -> Result<Vec<FrecentItem>> {
+        let db = self.index.db.lock().expect("Apps Mutex poisoned");
+        frecent_items(&*db, num_items)
+    }
+
+    fn actions(&self, _: &Config, id: u64) -> Result<Vec<Action>> {
+        let pinned = self
+            .index
             .db
             .lock()
             .expect("Apps Mutex poisoned")
-            .rescan_desktop_entries(&self.app_paths)?;
-        Ok(errors)
+            .is_pinned(id);
+        Ok(vec![
+            Action::new("terminal", "Run in terminal"),
+            Action::new("show_desktop_file", "Show desktop file"),
+            if pinned {
+                Action::new("pin", "Unpin")
+            } else {
+                Action::new("pin", "Pin")
+            },
+        ])
+    }
+
+    fn run_action(
+        &self,
+        config: &Config,
+        id: u64,
+        action: &str,
+    ) -> Result<ActionResult> {
+        let cont = self
+            .index
+            .db
+            .lock()
+            .expect("Apps Mutex poisoned")
+            .get_by_id(id)?
+            .ok_or_else(|| anyhow!("No app with id {}", id))?;
+        match action {
+            "terminal" => cont.item.run_in_terminal(&self.term_cmd)?,
+            "show_desktop_file" => {
+                let path = &cont.item.path;
+                if path.as_os_str().is_empty() {
+                    return Err(anyhow!(
+                        "The desktop file of {} isn't known yet, reload the \
+                         apps to find it",
+                        cont.item.name
+                    ));
+                }
+                match mime::default_app("text/plain") {
+                    Some(editor) => editor.open_file(&self.term_cmd, path)?,
+                    None => xdg_open(path.parent().unwrap_or(path))?,
+                }
+            }
+            "pin" => {
+                self.toggle_pin(config, id)?;
+                return Ok(ActionResult::Refresh);
+            }
+            _ => return Err(anyhow!("Unknown action {}", action)),
+        }
+        Ok(ActionResult::Hide)
+    }
+
+    fn toggle_pin(&self, _: &Config, id: u64) -> Result<bool> {
+        self.index
+            .db
+            .lock()
+            .expect("Apps Mutex poisoned")
+            .toggle_pinned(id)
+    }
+
+    fn add_alias(&self, _: &Config, alias: &str, id: u64) -> Result<()> {
+        self.index
+            .db
+            .lock()
+            .expect("Apps Mutex poisoned")
+            .add_alias(alias, id)
+    }
+
+    fn reload(&self, config: &Config) -> Result<Vec<Error>> {
+        self.index.rescan(&config.file_options.aliases)
     }
 
     fn register_event_handlers(
-        &mut self,
-        _config: &Config,
+        &self,
+        config: &Config,
         event_tx: Sender<Event>,
     ) {
         let (tx, rx) = mpsc::channel();
@@ -161,7 +434,7 @@ impl Plugin for Apps {
                 return;
             }
         };
-        for path in &self.app_paths {
+        for path in &self.index.app_paths {
             let expanded = match shellexpand::full(&path) {
                 Ok(path) => path.into_owned(),
                 Err(e) => {
@@ -189,12 +462,20 @@ impl Plugin for Apps {
             }
         }
         std::mem::forget(watcher);
+        let index = self.index.clone();
+        let aliases = config.file_options.aliases.clone();
         thread::spawn(move || loop {
             match rx.recv() {
                 Ok(event) => {
                     debug!("Desktop file watcher received: {:?}", event);
-                    if let Err(e) = event_tx.send(Event::Reload) {
-                        error!("Error sending event to ui: {:?}", e);
+                    match index.apply(event, &aliases) {
+                        Ok(true) => {
+                            if let Err(e) = event_tx.send(Event::Refresh) {
+                                error!("Error sending event to ui: {:?}", e);
+                            }
+                        }
+                        Ok(false) => {}
+                        Err(e) => error!("{:?}", e),
                     }
                 }
                 Err(e) => {
@@ -219,6 +500,16 @@ pub struct App {
     pub icon: String,
     /// If true, launch in terminal
     pub(crate) terminal: bool,
+    /// Path of the desktop file the app was read from.
+    #[serde(default)]
+    pub(crate) path: PathBuf,
+    /// Short description of the app.
+    #[serde(default)]
+    pub comment: String,
+    /// The exec string as written in the desktop file, with the field
+    /// codes like `%U` saying where files go.
+    #[serde(default)]
+    pub(crate) raw_exec: String,
 }
 
 impl App {
@@ -234,14 +525,34 @@ impl App {
             icon,
             exec,
             terminal,
+            path: PathBuf::new(),
+            comment: String::new(),
+            raw_exec: String::new(),
         }
     }
 
-    /// Set this app's name, icon, and exec to the values of the other app.
+    /// Whether the app was installed with flatpak.
+    pub fn is_flatpak(&self) -> bool {
+        self.path.to_string_lossy().contains("/flatpak/")
+            || self.exec.starts_with("flatpak ")
+            || self.exec.contains("/flatpak ")
+    }
+
+    /// Whether the app was installed with snap.
+    pub fn is_snap(&self) -> bool {
+        self.path.to_string_lossy().contains("/snapd/")
+            || self.exec.contains("/snap/bin/")
+    }
+
+    /// Set this app's name, icon, exec strings and path to the values of
+    /// the other app.
     pub fn merge(&mut self, other: &App) {
         self.name = other.name.clone();
         self.icon = other.icon.clone();
         self.exec = other.exec.clone();
+        self.raw_exec = other.raw_exec.clone();
+        self.path = other.path.clone();
+        self.comment = other.comment.clone();
     }
 }
 
@@ -292,10 +603,55 @@ impl DBItem for App {
 
 impl From<Container<App>> for ListItem {
     fn from(cont: Container<App>) -> Self {
+        let app = &cont.item;
+        let badges = [
+            (app.is_flatpak(), Badge::Flatpak),
+            (app.is_snap(), Badge::Snap),
+            (app.terminal, Badge::Terminal),
+            (cont.pinned, Badge::Pinned),
+        ]
+        .iter()
+        .filter(|(has, _)| *has)
+        .map(|&(_, badge)| badge)
+        .collect();
         Self {
-            name: cont.item.name.clone(),
-            icon: cont.item.icon.clone(),
+            name: app.name.clone(),
+            icon: app.icon.clone(),
             id: cont.id,
+            matches: index_ranges(&cont.indices),
+            subtitle: app.comment.clone(),
+            source: String::new(),
+            badges,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn badges() {
+        let mut app = App::new(
+            "Terminal".into(),
+            "terminal".into(),
+            "/usr/bin/flatpak run org.gnome.Terminal".into(),
+            false,
+        );
+        app.path =
+            "/var/lib/flatpak/exports/share/applications/a.desktop".into();
+        let cont = Container {
+            id: 1,
+            score: 0.0,
+            indices: vec![],
+            pinned: true,
+            item: app,
+        };
+        let item = ListItem::from(cont);
+        assert_eq!(item.badges, vec![Badge::Flatpak, Badge::Pinned]);
+
+        let mut vim = App::new("Vim".into(), "".into(), "vim".into(), true);
+        vim.path = "/var/lib/snapd/desktop/applications/vim.desktop".into();
+        assert!(vim.is_snap() && !vim.is_flatpak());
+    }
+}
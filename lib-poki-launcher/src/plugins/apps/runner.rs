@@ -17,6 +17,8 @@
 use crate::run::*;
 use anyhow::{Context as _, Result};
 use log::debug;
+use std::ffi::OsStr;
+use std::path::Path;
 use std::process::Command;
 
 use super::App;
@@ -44,16 +46,77 @@ fn with_term<'a>(
     }
 }
 
+/// Put the file an app opens in place of the file field code of its exec
+/// string's arguments, removing the other field codes.
+///
+/// The file goes at the end if the exec string has no file field code.
+fn expand_field_codes<'a>(
+    args: &[&'a str],
+    file: Option<&'a Path>,
+) -> Vec<&'a OsStr> {
+    let mut file = file;
+    let mut expanded = Vec::with_capacity(args.len() + 1);
+    for &arg in args {
+        match arg {
+            "%f" | "%F" | "%u" | "%U" => {
+                if let Some(file) = file.take() {
+                    expanded.push(file.as_os_str());
+                }
+            }
+            code if code.starts_with('%') => {}
+            arg => expanded.push(OsStr::new(arg)),
+        }
+    }
+    if let Some(file) = file {
+        expanded.push(file.as_os_str());
+    }
+    expanded
+}
+
 impl App {
     /// Run the app.
     pub fn run(&self, term_cmd: &Option<String>) -> Result<()> {
-        debug!("Exec: `{}`", self.exec);
-        let (cmd, args) = if self.terminal {
-            with_term(&term_cmd, &self.exec)?
+        self.run_with(term_cmd, self.terminal, None)
+    }
+
+    /// Run the app in a terminal even if it isn't a terminal app.
+    pub fn run_in_terminal(&self, term_cmd: &Option<String>) -> Result<()> {
+        self.run_with(term_cmd, true, None)
+    }
+
+    /// Run the app to open a file.
+    pub fn open_file(
+        &self,
+        term_cmd: &Option<String>,
+        file: &Path,
+    ) -> Result<()> {
+        self.run_with(term_cmd, self.terminal, Some(file))
+    }
+
+    fn run_with(
+        &self,
+        term_cmd: &Option<String>,
+        terminal: bool,
+        file: Option<&Path>,
+    ) -> Result<()> {
+        let exec = if self.raw_exec.is_empty() {
+            &self.exec
+        } else {
+            &self.raw_exec
+        };
+        debug!("Exec: `{}`", exec);
+        let (cmd, args) = if terminal {
+            with_term(term_cmd, exec)?
         } else {
-            parse_command_string(&self.exec)
+            parse_command_string(exec)
         };
-        debug!("Running `{} {}`", cmd, args.join(" "));
+        let args = expand_field_codes(&args, file);
+        let line = args
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        debug!("Running `{} {}`", cmd, line);
         let mut command = Command::new(&cmd);
         command.args(&args);
         let _ = run_bg(command).with_context(|| {
@@ -62,10 +125,47 @@ impl App {
             If I'm trying to start your terminal emulator with \
             the wrong options please set term_cmd in the config \
             file with the correct command",
-                cmd,
-                args.join(" ")
+                cmd, line
             )
         })?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand<'a>(exec: &'a str, file: Option<&'a str>) -> Vec<&'a OsStr> {
+        let (_, args) = parse_command_string(exec);
+        expand_field_codes(&args, file.map(Path::new))
+    }
+
+    #[test]
+    fn field_codes() {
+        assert_eq!(expand("gimp-2.10 %U", None), Vec::<&OsStr>::new());
+        assert_eq!(
+            expand("code --new-window %F --wait", Some("/a b.txt")),
+            vec!["--new-window", "/a b.txt", "--wait"]
+        );
+        assert_eq!(expand("gedit", Some("/notes")), vec!["/notes"]);
+        assert_eq!(
+            expand(
+                "flatpak run --file-forwarding org.gimp.GIMP @@u %U @@",
+                Some("/shot.png")
+            ),
+            vec![
+                "run",
+                "--file-forwarding",
+                "org.gimp.GIMP",
+                "@@u",
+                "/shot.png",
+                "@@"
+            ]
+        );
+        assert_eq!(
+            expand("app --icon %i %c %f", Some("/file")),
+            vec!["--icon", "/file"]
+        );
+    }
+}
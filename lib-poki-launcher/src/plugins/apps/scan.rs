@@ -14,9 +14,16 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
-use super::{App, AppsDB};
-use anyhow::{Error, Result};
+use super::App;
+use anyhow::{Context as _, Error, Result};
+use log::{debug, warn};
+use rayon::prelude::*;
+use rmp_serde as rmp;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use thiserror::Error;
 use walkdir::WalkDir;
 
@@ -32,95 +39,275 @@ pub enum ScanError {
 }
 
 /// Get a list of desktop entries from a list of directories to search.
+///
+/// The directories are searched in parallel.
 pub fn desktop_entires(paths: &[String]) -> (Vec<PathBuf>, Vec<Error>) {
+    paths.par_iter().map(|loc| dir_desktop_entries(loc)).reduce(
+        || (Vec::new(), Vec::new()),
+        |(mut files, mut errors), (more_files, more_errors)| {
+            files.extend(more_files);
+            errors.extend(more_errors);
+            (files, errors)
+        },
+    )
+}
+
+/// Get the desktop entries in a directory.
+fn dir_desktop_entries(loc: &str) -> (Vec<PathBuf>, Vec<Error>) {
+    match shellexpand::full(loc) {
+        Ok(expanded) => path_desktop_entries(Path::new(&*expanded)),
+        Err(e) => (
+            Vec::new(),
+            vec![ScanError::PathExpand {
+                path: loc.to_owned(),
+                err: e.into(),
+            }
+            .into()],
+        ),
+    }
+}
+
+/// Get the desktop entries in a directory and its subdirectories, or the
+/// path itself if it's a desktop entry.
+pub fn path_desktop_entries(path: &Path) -> (Vec<PathBuf>, Vec<Error>) {
     let mut files = Vec::new();
     let mut errors = Vec::new();
-    for loc in paths {
-        let expanded = match shellexpand::full(&loc) {
-            Ok(path) => path,
-            Err(e) => {
-                errors.push(
-                    ScanError::PathExpand {
-                        path: loc.clone(),
-                        err: e.into(),
-                    }
-                    .into(),
-                );
-                continue;
-            }
-        };
-        for entry in WalkDir::new(&*expanded) {
-            match entry {
-                Ok(entry) => {
-                    if entry.file_name().to_str().unwrap().contains(".desktop")
-                    {
-                        files.push(entry.path().to_owned())
-                    }
-                }
-                Err(e) => {
-                    errors.push(
-                        ScanError::ScanDirectory {
-                            dir: loc.clone(),
-                            err: e.into(),
-                        }
-                        .into(),
-                    );
-                    continue;
+    for entry in WalkDir::new(path) {
+        match entry {
+            Ok(entry) => {
+                if is_desktop_file(entry.path()) {
+                    files.push(entry.path().to_owned())
                 }
             }
+            Err(e) => errors.push(
+                ScanError::ScanDirectory {
+                    dir: path.display().to_string(),
+                    err: e.into(),
+                }
+                .into(),
+            ),
         }
     }
     (files, errors)
 }
 
-/// Get a list of apps for a list of paths to search.
-pub fn scan_desktop_entries(paths: &[String]) -> (Vec<App>, Vec<Error>) {
-    let (entries, mut errors) = desktop_entires(&paths);
-    let (apps, errs): (Vec<_>, Vec<_>) = entries
-        .into_iter()
-        .map(|path| App::parse_desktop_file(&path))
-        .partition(Result::is_ok);
-    let mut apps: Vec<_> = apps
-        .into_iter()
-        .map(Result::unwrap)
-        .filter_map(|x| x)
-        .collect();
-    apps.sort_unstable();
-    apps.dedup();
-    errors.extend(errs.into_iter().map(Result::unwrap_err).collect::<Vec<_>>());
-    (apps, errors)
+/// Whether a file is a desktop entry.
+pub fn is_desktop_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.contains(".desktop"))
+        .unwrap_or(false)
+}
+
+/// The desktop files found by the last scan and the apps read from them.
+///
+/// This is saved between runs so scans only parse the files that were
+/// modified since the last scan.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DesktopFiles {
+    files: HashMap<PathBuf, DesktopFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DesktopFile {
+    /// When the file was modified when it was read.
+    modified: SystemTime,
+    /// `None` if the file isn't shown as an app.
+    app: Option<App>,
 }
 
-impl AppsDB {
-    /// Create an `AppsDB` from the desktop entries.
+impl DesktopFile {
+    fn read(path: &Path) -> Result<Self> {
+        Ok(DesktopFile {
+            modified: modified(path)?,
+            app: App::parse_desktop_file(path)?,
+        })
+    }
+}
+
+/// Get when a file was last modified.
+fn modified(path: &Path) -> Result<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| {
+            format!("Error reading desktop file {}", path.display())
+        })
+}
+
+impl DesktopFiles {
+    /// Load the desktop files saved by `save`.
     ///
-    /// # Arguments
+    /// Starts empty if nothing was saved or it can't be read.
+    pub fn load(path: &Path) -> Self {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(_) => return Self::default(),
+        };
+        rmp::from_slice(&data).unwrap_or_else(|e| {
+            warn!("Ignoring desktop file cache {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    /// Save the desktop files for the next run.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, rmp::to_vec(self)?).with_context(|| {
+            format!("Error saving desktop file cache {}", path.display())
+        })
+    }
+
+    /// Scan for desktop files in a list of directories, only parsing the
+    /// files that are new or were modified since they were last read.
     ///
-    /// * `paths` - A list of paths to desktop entries.
-    pub fn from_desktop_entries(
-        db_path: impl AsRef<Path>,
-        app_paths: &Vec<String>,
-    ) -> Result<(AppsDB, Vec<Error>)> {
-        let (apps, errors) = scan_desktop_entries(app_paths);
-        let mut db = AppsDB::new(db_path)?;
-        db.merge_new_entries(&apps)?;
-        Ok((db, errors))
+    /// The files are read in parallel.
+    pub fn scan(&mut self, paths: &[String]) -> Vec<Error> {
+        let (found, mut errors) = desktop_entires(paths);
+        let cached = &self.files;
+        let results: Vec<_> = found
+            .into_par_iter()
+            .map(|path| match (modified(&path), cached.get(&path)) {
+                (Ok(modified), Some(file)) if file.modified == modified => {
+                    Ok((path, file.clone(), false))
+                }
+                _ => DesktopFile::read(&path).map(|file| (path, file, true)),
+            })
+            .collect();
+        let mut files = HashMap::with_capacity(results.len());
+        let mut parsed = 0;
+        for result in results {
+            match result {
+                Ok((path, file, was_parsed)) => {
+                    if was_parsed {
+                        parsed += 1;
+                    }
+                    files.insert(path, file);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+        debug!("Parsed {} of {} desktop files", parsed, files.len());
+        self.files = files;
+        errors
     }
 
-    /// Update self with new desktop entries.
+    /// Read a desktop file that was created or modified.
     ///
-    /// Scan the desktop entries again then merge the new list
-    /// into self with `AppsDB.merge`.
+    /// Returns the app the file used to have.
+    pub fn update(&mut self, path: &Path) -> Result<Option<App>> {
+        let file = DesktopFile::read(path)?;
+        Ok(self
+            .files
+            .insert(path.to_owned(), file)
+            .and_then(|file| file.app))
+    }
+
+    /// Forget the desktop files at or under a path that was removed.
     ///
-    /// # Arguments
+    /// Returns the apps the files had.
+    pub fn remove(&mut self, path: &Path) -> Vec<App> {
+        let mut apps = Vec::new();
+        self.files.retain(|file_path, file| {
+            if file_path.starts_with(path) {
+                apps.extend(file.app.take());
+                false
+            } else {
+                true
+            }
+        });
+        apps
+    }
+
+    /// The app of a desktop file.
+    pub fn app(&self, path: &Path) -> Option<&App> {
+        self.files.get(path).and_then(|file| file.app.as_ref())
+    }
+
+    /// Find a desktop file with an app equal to `app`.
     ///
-    /// * `paths` - A list of paths to desktop entries.
-    pub fn rescan_desktop_entries(
-        &mut self,
-        app_paths: &Vec<String>,
-    ) -> Result<Vec<Error>> {
-        let (apps, errors) = scan_desktop_entries(app_paths);
-        self.merge_new_entries(&apps)?;
-        Ok(errors)
+    /// Several desktop files can have the same app, such as a system wide
+    /// one and a user's copy of it.
+    pub fn find(&self, app: &App) -> Option<&App> {
+        self.files
+            .values()
+            .filter_map(|file| file.app.as_ref())
+            .find(|other| *other == app)
+    }
+
+    /// The apps of all the desktop files without duplicates.
+    pub fn apps(&self) -> Vec<App> {
+        let mut apps: Vec<App> = self
+            .files
+            .values()
+            .filter_map(|file| file.app.clone())
+            .collect();
+        apps.sort_unstable();
+        apps.dedup();
+        apps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::fs::File;
+    use std::io::Write;
+
+    fn write_entry(path: &Path, name: &str) {
+        let mut file = File::create(path).unwrap();
+        write!(file, "[Desktop Entry]\nName={}\nExec={}\n", name, name)
+            .unwrap();
+    }
+
+    #[test]
+    fn desktop_files() {
+        let dir = TestDir::new("desktop-files");
+        let paths = vec![dir.to_string_lossy().into_owned()];
+        let first = dir.join("first.desktop");
+        write_entry(&first, "First");
+        write_entry(&dir.join("second.desktop"), "Second");
+        let sub = dir.join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        write_entry(&sub.join("third.desktop"), "Third");
+
+        let mut files = DesktopFiles::default();
+        assert!(files.scan(&paths).is_empty());
+        assert_eq!(files.apps().len(), 3);
+        assert_eq!(
+            path_desktop_entries(&sub).0,
+            vec![sub.join("third.desktop")]
+        );
+
+        // Removing a directory removes the desktop files in it
+        fs::remove_dir_all(&sub).unwrap();
+        let removed = files.remove(&sub);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].name, "Third");
+        assert_eq!(files.apps().len(), 2);
+
+        // Unmodified files aren't parsed again
+        files
+            .files
+            .get_mut(&first)
+            .unwrap()
+            .app
+            .as_mut()
+            .unwrap()
+            .name = "Cached".to_owned();
+        files.scan(&paths);
+        assert_eq!(files.app(&first).unwrap().name, "Cached");
+
+        write_entry(&first, "Changed");
+        let old = files.update(&first).unwrap().unwrap();
+        assert_eq!(old.name, "Cached");
+        assert_eq!(files.app(&first).unwrap().name, "Changed");
+        assert!(files.find(files.app(&first).unwrap()).is_some());
+
+        let cache = dir.join("cache");
+        files.save(&cache).unwrap();
+        let mut loaded = DesktopFiles::load(&cache);
+        assert_eq!(loaded.apps(), files.apps());
+        assert_eq!(loaded.remove(&first)[0].name, "Changed");
+        assert_eq!(loaded.apps().len(), 1);
     }
 }
@@ -0,0 +1,246 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::App;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+lazy_static! {
+    /// Pinyin reading of each character in the bundled table.
+    static ref PINYIN: HashMap<char, &'static str> = {
+        let mut pinyin = HashMap::new();
+        for line in include_str!("pinyin.txt").lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let syllable = parts.next();
+            let chars = parts.next();
+            if let (Some(syllable), Some(chars)) = (syllable, chars) {
+                for c in chars.chars() {
+                    pinyin.entry(c).or_insert(syllable);
+                }
+            }
+        }
+        pinyin
+    };
+}
+
+/// Hepburn romanization of hiragana.
+///
+/// Small kana are listed with the reading they have on their own.
+#[rustfmt::skip]
+const HIRAGANA: &[(char, &str)] = &[
+    ('あ', "a"), ('い', "i"), ('う', "u"), ('え', "e"), ('お', "o"),
+    ('ぁ', "a"), ('ぃ', "i"), ('ぅ', "u"), ('ぇ', "e"), ('ぉ', "o"),
+    ('か', "ka"), ('き', "ki"), ('く', "ku"), ('け', "ke"), ('こ', "ko"),
+    ('が', "ga"), ('ぎ', "gi"), ('ぐ', "gu"), ('げ', "ge"), ('ご', "go"),
+    ('さ', "sa"), ('し', "shi"), ('す', "su"), ('せ', "se"), ('そ', "so"),
+    ('ざ', "za"), ('じ', "ji"), ('ず', "zu"), ('ぜ', "ze"), ('ぞ', "zo"),
+    ('た', "ta"), ('ち', "chi"), ('つ', "tsu"), ('て', "te"), ('と', "to"),
+    ('だ', "da"), ('ぢ', "ji"), ('づ', "zu"), ('で', "de"), ('ど', "do"),
+    ('な', "na"), ('に', "ni"), ('ぬ', "nu"), ('ね', "ne"), ('の', "no"),
+    ('は', "ha"), ('ひ', "hi"), ('ふ', "fu"), ('へ', "he"), ('ほ', "ho"),
+    ('ば', "ba"), ('び', "bi"), ('ぶ', "bu"), ('べ', "be"), ('ぼ', "bo"),
+    ('ぱ', "pa"), ('ぴ', "pi"), ('ぷ', "pu"), ('ぺ', "pe"), ('ぽ', "po"),
+    ('ま', "ma"), ('み', "mi"), ('む', "mu"), ('め', "me"), ('も', "mo"),
+    ('や', "ya"), ('ゆ', "yu"), ('よ', "yo"),
+    ('ゃ', "ya"), ('ゅ', "yu"), ('ょ', "yo"),
+    ('ら', "ra"), ('り', "ri"), ('る', "ru"), ('れ', "re"), ('ろ', "ro"),
+    ('わ', "wa"), ('ゎ', "wa"), ('ゐ', "i"), ('ゑ', "e"), ('を', "o"),
+    ('ん', "n"), ('ゔ', "vu"), ('ゕ', "ka"), ('ゖ', "ke"),
+];
+
+/// Convert katakana to the matching hiragana.
+fn to_hiragana(c: char) -> char {
+    match c {
+        'ァ'..='ヶ' => std::char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn kana_romaji(c: char) -> Option<&'static str> {
+    let c = to_hiragana(c);
+    HIRAGANA
+        .iter()
+        .find(|(kana, _)| *kana == c)
+        .map(|(_, romaji)| *romaji)
+}
+
+/// Romanize the kana in `text`, leaving other characters as they are.
+///
+/// Returns `None` if `text` has no kana.
+pub fn romaji(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut found = false;
+    // Set by a small tsu, which doubles the next consonant
+    let mut double = false;
+    // The previous kana, as hiragana
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        let hiragana = to_hiragana(c);
+        if hiragana == 'っ' {
+            found = true;
+            double = true;
+            continue;
+        }
+        if c == 'ー' {
+            // Long vowel mark, repeat the previous vowel
+            if let Some(last) = out.chars().last() {
+                if "aiueo".contains(last) {
+                    out.push(last);
+                }
+            }
+            continue;
+        }
+        let romaji = match kana_romaji(c) {
+            Some(romaji) => romaji,
+            None => {
+                double = false;
+                prev = None;
+                out.push(c);
+                continue;
+            }
+        };
+        found = true;
+        match hiragana {
+            // Small ya, yu and yo combine with the preceding i kana:
+            // きゃ is kya, しゃ is sha and じゃ is ja.
+            'ゃ' | 'ゅ' | 'ょ' if out.ends_with('i') => {
+                out.pop();
+                if !(out.ends_with("sh")
+                    || out.ends_with("ch")
+                    || out.ends_with('j'))
+                {
+                    out.push('y');
+                }
+                out.push_str(&romaji[1..]);
+            }
+            // Small vowels replace the vowel of the preceding kana:
+            // ファ is fa, ティ is ti and ウィ is wi.
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ'
+                if prev.is_some()
+                    && out.ends_with(|c: char| "aiueo".contains(c)) =>
+            {
+                out.pop();
+                if prev == Some('う') {
+                    out.push('w');
+                }
+                out.push_str(romaji);
+            }
+            _ => {
+                if double {
+                    out.push_str(&romaji[..1]);
+                }
+                out.push_str(romaji);
+            }
+        }
+        double = false;
+        prev = Some(hiragana);
+    }
+    if found {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+/// Convert the Chinese characters in `text` to pinyin, leaving other
+/// characters as they are.
+///
+/// Returns the syllables separated by spaces and their initials, or
+/// `None` if `text` has no characters in the pinyin table.
+pub fn pinyin(text: &str) -> Option<(String, String)> {
+    let mut full = String::with_capacity(text.len() * 2);
+    let mut initials = String::new();
+    let mut found = false;
+    // Whether the last character was a converted syllable
+    let mut after_syllable = false;
+    for c in text.chars() {
+        match PINYIN.get(&c) {
+            Some(syllable) => {
+                if !full.is_empty() && !full.ends_with(' ') {
+                    full.push(' ');
+                }
+                full.push_str(syllable);
+                initials.extend(syllable.chars().next());
+                found = true;
+                after_syllable = true;
+            }
+            None => {
+                if after_syllable && !c.is_whitespace() {
+                    full.push(' ');
+                }
+                full.push(c);
+                if !c.is_whitespace() {
+                    initials.push(c);
+                }
+                after_syllable = false;
+            }
+        }
+    }
+    if found {
+        Some((full, initials))
+    } else {
+        None
+    }
+}
+
+/// Transliterations of an app's name that it can also be found by.
+pub fn alternate_names(app: &App) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some((full, initials)) = pinyin(&app.name) {
+        names.push(full);
+        names.push(initials);
+    }
+    if let Some(romaji) = romaji(&app.name) {
+        names.push(romaji);
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinyin_names() {
+        assert_eq!(
+            pinyin("网易云音乐"),
+            Some(("wang yi yun yin yue".to_owned(), "wyyyy".to_owned()))
+        );
+        assert_eq!(
+            pinyin("QQ音乐"),
+            Some(("QQ yin yue".to_owned(), "QQyy".to_owned()))
+        );
+        assert_eq!(pinyin("Firefox"), None);
+    }
+
+    #[test]
+    fn romaji_names() {
+        assert_eq!(romaji("ひらがな").as_deref(), Some("hiragana"));
+        assert_eq!(
+            romaji("テキストエディター").as_deref(),
+            Some("tekisutoeditaa")
+        );
+        assert_eq!(romaji("しゃしん").as_deref(), Some("shashin"));
+        assert_eq!(romaji("きょう").as_deref(), Some("kyou"));
+        assert_eq!(romaji("ざっし").as_deref(), Some("zasshi"));
+        assert_eq!(romaji("ファイル").as_deref(), Some("fairu"));
+        assert_eq!(romaji("ウィンドウ").as_deref(), Some("windou"));
+        assert_eq!(romaji("Firefox"), None);
+    }
+}
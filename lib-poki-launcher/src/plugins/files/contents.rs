@@ -0,0 +1,707 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{File, FileIndex, FileKind, FileListener, FilesOptions};
+use crate::cancel::CancelToken;
+use crate::config::Config;
+use crate::event::Event;
+use crate::plugins::{Plugin, PluginOptions};
+use crate::run::xdg_open;
+use crate::{Action, ActionResult, ListItem};
+use anyhow::{anyhow, Context as _, Error, Result};
+use log::{debug, warn};
+use rayon::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension, NO_PARAMS};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error as ThisError;
+
+/// How many words of a document are shown around the words that matched.
+const SNIPPET_WORDS: usize = 12;
+
+/// How many documents are read and stored at a time, so the text of every
+/// changed file isn't held at once and searches run between batches.
+const BATCH_SIZE: usize = 200;
+
+/// Extensions of the text files indexed by default.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "rst", "org", "tex", "csv", "json", "toml",
+    "yaml", "yml", "hjson", "ini", "conf", "xml", "html", "css", "sh", "py",
+    "rs", "c", "h", "cpp", "hpp", "go", "java", "js", "ts", "rb", "lua", "sql",
+];
+
+/// Searches the text inside the files the files plugin indexes.
+///
+/// The text is kept in an SQLite full-text index next to `files.db`,
+/// updated as the files plugin's index finds files.
+pub struct Contents {
+    index: Arc<ContentIndex>,
+    files: Arc<FileIndex>,
+    /// Whether this plugin rescans and watches the files, which the files
+    /// plugin does if it's loaded.
+    scans: bool,
+    watch: bool,
+    rescan_interval: Duration,
+}
+
+/// Options of the contents plugin.
+///
+/// Which files are indexed is set by the options of the files plugin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ContentsOptions {
+    /// Extensions of the text files to index.
+    pub extensions: Vec<String>,
+    /// Commands printing the text of other types of files, by extension.
+    ///
+    /// `{}` in the arguments is replaced with the path of the file.
+    pub extractors: BTreeMap<String, Vec<String>>,
+    /// Biggest file to index, in bytes.
+    pub max_size: u64,
+}
+
+impl Default for ContentsOptions {
+    fn default() -> Self {
+        let command = |args: &[&str]| {
+            args.iter().map(|arg| (*arg).to_owned()).collect::<Vec<_>>()
+        };
+        let mut extractors = BTreeMap::new();
+        extractors.insert(
+            "pdf".to_owned(),
+            command(&["pdftotext", "-q", "-enc", "UTF-8", "{}", "-"]),
+        );
+        extractors.insert("odt".to_owned(), command(&["odt2txt", "{}"]));
+        ContentsOptions {
+            extensions: TEXT_EXTENSIONS.iter().map(|&ext| ext.into()).collect(),
+            extractors,
+            max_size: 10_000_000,
+        }
+    }
+}
+
+/// The full-text index, shared with the files index.
+struct ContentIndex {
+    conn: Mutex<Connection>,
+    options: ContentsOptions,
+}
+
+/// A document found by a search.
+#[derive(Debug, Clone, PartialEq)]
+struct Hit {
+    id: i64,
+    path: PathBuf,
+    snippet: String,
+}
+
+/// A file to index and when it was last changed.
+struct Document {
+    path: PathBuf,
+    modified: u64,
+    size: u64,
+}
+
+impl From<&File> for Document {
+    fn from(file: &File) -> Self {
+        Document {
+            path: file.path.clone(),
+            modified: file.modified,
+            size: file.size,
+        }
+    }
+}
+
+/// The program of an extractor isn't installed.
+#[derive(Debug, ThisError)]
+#[error("`{0}` isn't installed")]
+struct MissingTool(String);
+
+/// The errors from extracting the text of documents.
+#[derive(Default)]
+struct Extraction {
+    errors: Vec<Error>,
+    /// Extensions whose extractor isn't installed.
+    missing: HashSet<String>,
+}
+
+impl Contents {
+    /// Load the plugin, sharing the files index in `loaded` if the files
+    /// plugin loaded it first.
+    pub fn init(
+        config: &Config,
+        options: &PluginOptions<ContentsOptions>,
+        loaded: &mut Option<Arc<FileIndex>>,
+    ) -> Result<(Self, Vec<Error>)> {
+        let plugins = &config.file_options.plugins;
+        let files = PluginOptions::<FilesOptions>::from_config_lenient(
+            plugins, "files",
+        )?;
+        let (file_index, errors) = FileIndex::shared(config, &files, loaded)?;
+        let conn = open_db(&config.data_dir.join("contents.db"))?;
+        let index = Arc::new(ContentIndex {
+            conn: Mutex::new(conn),
+            options: options.plugin.clone(),
+        });
+        file_index.listen(index.clone());
+        let load_order = &config.file_options.plugin_load_order;
+        Ok((
+            Contents {
+                index,
+                files: file_index,
+                scans: !load_order.iter().any(|name| name == "files"),
+                watch: files.plugin.watch,
+                rescan_interval: Duration::from_secs(
+                    files.plugin.rescan_interval.max(1) * 60,
+                ),
+            },
+            errors,
+        ))
+    }
+}
+
+/// Open the database, creating its tables if they don't exist.
+fn open_db(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path).with_context(|| {
+        format!("Error opening contents database {}", path.display())
+    })?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS documents (
+             id        INTEGER PRIMARY KEY,
+             path      TEXT NOT NULL UNIQUE,
+             modified  INT NOT NULL,
+             size      INT NOT NULL
+         );
+         CREATE VIRTUAL TABLE IF NOT EXISTS contents
+             USING fts5(name, body, tokenize = 'unicode61');",
+    )
+    .context("Error creating the contents database, is FTS5 available?")?;
+    Ok(conn)
+}
+
+/// Turn a search into an FTS5 query finding documents containing words
+/// starting with each of its words.
+fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Replace runs of whitespace, like line breaks, with single spaces.
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl ContentIndex {
+    /// The extension of a file if its text can be indexed.
+    fn indexed_extension(&self, path: &Path) -> Option<String> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        if self.options.extensions.contains(&ext)
+            || self.options.extractors.contains_key(&ext)
+        {
+            Some(ext)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a file found by the walker should have its text indexed.
+    fn indexes(&self, file: &File) -> bool {
+        file.kind == FileKind::File
+            && file.size <= self.options.max_size
+            && self.indexed_extension(&file.path).is_some()
+    }
+
+    /// Get the text of a document, or `None` if it isn't text.
+    fn extract(&self, path: &Path, ext: &str) -> Result<Option<String>> {
+        let bytes = match self.options.extractors.get(ext) {
+            Some(command) => {
+                let (program, args) =
+                    command.split_first().ok_or_else(|| {
+                        anyhow!("The extractor for .{} files is empty", ext)
+                    })?;
+                let path = path.to_string_lossy();
+                let output = Command::new(program)
+                    .args(args.iter().map(|arg| arg.replace("{}", &path)))
+                    .output()
+                    .map_err(|e| match e.kind() {
+                        io::ErrorKind::NotFound => {
+                            Error::new(MissingTool(program.clone()))
+                        }
+                        _ => Error::new(e),
+                    })?;
+                if !output.status.success() {
+                    return Err(anyhow!(
+                        "`{}` failed: {}",
+                        program,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                output.stdout
+            }
+            None => fs::read(path)?,
+        };
+        // Binary files with a text extension
+        if bytes.iter().take(1024).any(|&byte| byte == 0) {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    /// Extract the text of a batch of documents in parallel.
+    fn extract_batch<'a>(
+        &self,
+        documents: &'a [Document],
+        extraction: &mut Extraction,
+    ) -> Vec<(&'a Document, String)> {
+        let results: Vec<_> = documents
+            .par_iter()
+            .filter_map(|document| {
+                let ext = self.indexed_extension(&document.path)?;
+                let text = self.extract(&document.path, &ext);
+                Some((document, ext, text))
+            })
+            .collect();
+        let mut texts = Vec::with_capacity(results.len());
+        for (document, ext, text) in results {
+            match text {
+                Ok(Some(text)) => texts.push((document, text)),
+                Ok(None) => {}
+                Err(e) => match e.downcast_ref::<MissingTool>() {
+                    Some(tool) => {
+                        if extraction.missing.insert(ext.clone()) {
+                            extraction.errors.push(anyhow!(
+                                "Can't index the text of .{} files: {}",
+                                ext,
+                                tool
+                            ));
+                        }
+                    }
+                    None => debug!(
+                        "Error reading the text of {}: {:?}",
+                        document.path.display(),
+                        e
+                    ),
+                },
+            }
+        }
+        texts
+    }
+
+    /// Extract and store the text of documents a batch at a time, only
+    /// locking the index while a batch is stored.
+    ///
+    /// Returns the errors and how many documents were stored.
+    fn index_documents(
+        &self,
+        documents: &[Document],
+    ) -> Result<(Vec<Error>, usize)> {
+        let mut extraction = Extraction::default();
+        let mut stored = 0;
+        for batch in documents.chunks(BATCH_SIZE) {
+            let texts = self.extract_batch(batch, &mut extraction);
+            let mut conn = self.conn.lock().expect("Contents Mutex poisoned");
+            let tx = conn.transaction()?;
+            for (document, text) in &texts {
+                store_document(&tx, document, text)?;
+            }
+            tx.commit()?;
+            stored += texts.len();
+        }
+        Ok((extraction.errors, stored))
+    }
+
+    /// Bring the index up to date with all the files under the roots,
+    /// reading only the files that changed since they were indexed.
+    fn rescan(&self, files: &[File]) -> Result<Vec<Error>> {
+        let indexed: HashMap<PathBuf, (u64, u64)> = {
+            let conn = self.conn.lock().expect("Contents Mutex poisoned");
+            let mut stmt =
+                conn.prepare("SELECT path, modified, size FROM documents;")?;
+            let rows = stmt.query_map(NO_PARAMS, |row| {
+                let path: String = row.get(0)?;
+                let modified: i64 = row.get(1)?;
+                let size: i64 = row.get(2)?;
+                Ok((PathBuf::from(path), (modified as u64, size as u64)))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let found: HashSet<PathBuf> = files
+            .iter()
+            .filter(|file| self.indexes(file))
+            .map(|file| file.path.clone())
+            .collect();
+        let changed: Vec<Document> = files
+            .iter()
+            .filter(|file| {
+                self.indexes(file)
+                    && indexed.get(&file.path)
+                        != Some(&(file.modified, file.size))
+            })
+            .map(Document::from)
+            .collect();
+        let removed: Vec<&PathBuf> = indexed
+            .keys()
+            .filter(|path| !found.contains(*path))
+            .collect();
+        debug!(
+            "Indexing the text of {} files, removing {}",
+            changed.len(),
+            removed.len()
+        );
+
+        {
+            let mut conn = self.conn.lock().expect("Contents Mutex poisoned");
+            let tx = conn.transaction()?;
+            for path in removed {
+                remove_document(&tx, path)?;
+            }
+            tx.commit()?;
+        }
+        Ok(self.index_documents(&changed)?.0)
+    }
+
+    /// Index the text of created or modified files.
+    fn update(&self, files: &[File]) -> Result<bool> {
+        let documents: Vec<Document> = files
+            .iter()
+            .filter(|file| self.indexes(file))
+            .map(Document::from)
+            .collect();
+        let (errors, stored) = self.index_documents(&documents)?;
+        for e in errors {
+            warn!("{:?}", e);
+        }
+        Ok(stored > 0)
+    }
+
+    /// Remove a deleted file or directory and the documents under it.
+    fn remove_path(&self, path: &Path) -> Result<bool> {
+        let conn = self.conn.lock().expect("Contents Mutex poisoned");
+        let dir = format!("{}/", path.to_string_lossy());
+        let mut stmt = conn.prepare(
+            "SELECT path FROM documents
+             WHERE path = ? OR substr(path, 1, length(?)) = ?;",
+        )?;
+        let paths = stmt
+            .query_map(params![path.to_string_lossy(), dir, dir], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for path in &paths {
+            remove_document(&conn, Path::new(path))?;
+        }
+        Ok(!paths.is_empty())
+    }
+
+    /// Find the documents containing the words of a search, best first.
+    fn search(&self, input: &str, num_items: usize) -> Result<Vec<Hit>> {
+        let query = match fts_query(input) {
+            Some(query) => query,
+            None => return Ok(Vec::new()),
+        };
+        let conn = self.conn.lock().expect("Contents Mutex poisoned");
+        let mut stmt = conn.prepare(
+            "SELECT documents.id, documents.path,
+                    snippet(contents, 1, '', '', '…', ?)
+             FROM contents JOIN documents ON documents.id = contents.rowid
+             WHERE contents MATCH ?
+             ORDER BY rank
+             LIMIT ?;",
+        )?;
+        let hits = stmt
+            .query_map(
+                params![SNIPPET_WORDS as i64, query, num_items as i64],
+                |row| {
+                    let path: String = row.get(1)?;
+                    let snippet: String = row.get(2)?;
+                    Ok(Hit {
+                        id: row.get(0)?,
+                        path: PathBuf::from(path),
+                        snippet: collapse_whitespace(&snippet),
+                    })
+                },
+            )?
+            .collect::<Result<_, _>>()?;
+        Ok(hits)
+    }
+
+    fn path(&self, id: u64) -> Result<PathBuf> {
+        let conn = self.conn.lock().expect("Contents Mutex poisoned");
+        let path: Option<String> = conn
+            .query_row(
+                "SELECT path FROM documents WHERE id = ?;",
+                params![id as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        path.map(PathBuf::from)
+            .ok_or_else(|| anyhow!("No document with id {}", id))
+    }
+}
+
+impl FileListener for ContentIndex {
+    fn rescanned(&self, files: &[File]) -> Result<Vec<Error>> {
+        self.rescan(files)
+    }
+
+    fn updated(&self, files: &[File]) -> Result<bool> {
+        self.update(files)
+    }
+
+    fn removed(&self, path: &Path) -> Result<bool> {
+        self.remove_path(path)
+    }
+}
+
+/// Add or replace the text of a document.
+fn store_document(
+    conn: &Connection,
+    document: &Document,
+    text: &str,
+) -> Result<()> {
+    let path = document.path.to_string_lossy();
+    let name = document
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    remove_document(conn, &document.path)?;
+    conn.execute(
+        "INSERT INTO documents (path, modified, size) VALUES (?, ?, ?);",
+        params![path, document.modified as i64, document.size as i64],
+    )?;
+    conn.execute(
+        "INSERT INTO contents (rowid, name, body) VALUES (?, ?, ?);",
+        params![conn.last_insert_rowid(), name, text],
+    )?;
+    Ok(())
+}
+
+/// Remove a document from the index.
+fn remove_document(conn: &Connection, path: &Path) -> Result<()> {
+    let id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM documents WHERE path = ?;",
+            params![path.to_string_lossy()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(id) = id {
+        conn.execute("DELETE FROM contents WHERE rowid = ?;", params![id])?;
+        conn.execute("DELETE FROM documents WHERE id = ?;", params![id])?;
+    }
+    Ok(())
+}
+
+impl From<Hit> for ListItem {
+    fn from(hit: Hit) -> Self {
+        ListItem {
+            name: hit
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            icon: "".to_owned(),
+            id: hit.id as u64,
+            matches: Vec::new(),
+            subtitle: hit.snippet,
+            source: String::new(),
+            badges: Vec::new(),
+        }
+    }
+}
+
+impl Plugin for Contents {
+    fn name(&self) -> &str {
+        "contents"
+    }
+
+    fn default_prefix(&self) -> Option<&str> {
+        Some("?")
+    }
+
+    fn search(
+        &self,
+        _: &Config,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ListItem>> {
+        if cancel.is_cancelled() {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .index
+            .search(input, num_items)?
+            .into_iter()
+            .map(ListItem::from)
+            .collect())
+    }
+
+    fn run(&self, _: &Config, id: u64) -> Result<()> {
+        xdg_open(&self.index.path(id)?)
+    }
+
+    fn actions(&self, _: &Config, _: u64) -> Result<Vec<Action>> {
+        Ok(vec![
+            Action::new("open_folder", "Open containing folder"),
+            Action::new("copy_path", "Copy path"),
+        ])
+    }
+
+    fn run_action(
+        &self,
+        _: &Config,
+        id: u64,
+        action: &str,
+    ) -> Result<ActionResult> {
+        let path = self.index.path(id)?;
+        match action {
+            "open_folder" => xdg_open(path.parent().unwrap_or(&path))?,
+            "copy_path" => {
+                return Ok(ActionResult::Copy(
+                    path.to_string_lossy().into_owned(),
+                ))
+            }
+            _ => return Err(anyhow!("Unknown action {}", action)),
+        }
+        Ok(ActionResult::Hide)
+    }
+
+    fn reload(&self, config: &Config) -> Result<Vec<Error>> {
+        if !self.scans {
+            return Ok(Vec::new());
+        }
+        self.files.rescan(&config.file_options.aliases)
+    }
+
+    fn register_event_handlers(
+        &self,
+        config: &Config,
+        event_tx: Sender<Event>,
+    ) {
+        if self.scans {
+            self.files.watch(
+                config,
+                self.watch,
+                self.rescan_interval,
+                event_tx,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::walk::Walker;
+    use super::super::FilesDB;
+    use super::*;
+    use crate::test_dir::TestDir;
+    use notify::DebouncedEvent;
+
+    fn names(index: &ContentIndex, search: &str) -> Vec<String> {
+        index
+            .search(search, 10)
+            .unwrap()
+            .into_iter()
+            .map(|hit| ListItem::from(hit).name)
+            .collect()
+    }
+
+    #[test]
+    fn query_words() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(
+            fts_query("quick \"fox"),
+            Some("\"quick\"* \"\"\"fox\"*".to_owned())
+        );
+    }
+
+    #[test]
+    fn content_index() {
+        let dir = TestDir::new("contents");
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::write(
+            root.join("notes/fox.md"),
+            "# Animals\n\nThe quick brown fox\njumps over the lazy dog",
+        )
+        .unwrap();
+        fs::write(root.join("notes/todo.txt"), "Buy a new dog bed").unwrap();
+        fs::write(root.join("photo.png"), "quick").unwrap();
+        fs::write(root.join("data.csv"), b"quick\0binary").unwrap();
+        fs::write(root.join("report.fake"), "").unwrap();
+
+        let files = FilesOptions {
+            roots: vec![root.to_string_lossy().into_owned()],
+            ..FilesOptions::default()
+        };
+        let mut options = ContentsOptions::default();
+        options
+            .extractors
+            .insert("fake".to_owned(), vec!["poki-no-such-tool".to_owned()]);
+        let index = Arc::new(ContentIndex {
+            conn: Mutex::new(open_db(&dir.join("contents.db")).unwrap()),
+            options,
+        });
+        let file_index = FileIndex {
+            db: Mutex::new(FilesDB::new(dir.join("files.db")).unwrap()),
+            walker: Walker::new(&files).0,
+            listeners: Mutex::new(Vec::new()),
+        };
+        file_index.listen(index.clone());
+        let aliases = HashMap::new();
+        let errors = file_index.rescan(&aliases).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("poki-no-such-tool"));
+
+        assert_eq!(names(&index, "quick"), vec!["fox.md"]);
+        assert_eq!(names(&index, "qui bro"), vec!["fox.md"]);
+        let hits = index.search("lazy", 10).unwrap();
+        assert!(hits[0].snippet.contains("jumps over the lazy dog"));
+        assert!(!hits[0].snippet.contains('\n'));
+        assert_eq!(
+            index.path(hits[0].id as u64).unwrap(),
+            root.join("notes/fox.md")
+        );
+        let mut dogs = names(&index, "dog");
+        dogs.sort();
+        assert_eq!(dogs, vec!["fox.md", "todo.txt"]);
+
+        fs::write(root.join("notes/todo.txt"), "Buy a cat tree").unwrap();
+        let event = DebouncedEvent::Write(root.join("notes/todo.txt"));
+        assert!(file_index.apply(event, &aliases).unwrap());
+        assert_eq!(names(&index, "dog"), vec!["fox.md"]);
+        assert_eq!(names(&index, "cat"), vec!["todo.txt"]);
+
+        fs::remove_dir_all(root.join("notes")).unwrap();
+        let event = DebouncedEvent::Remove(root.join("notes"));
+        assert!(file_index.apply(event, &aliases).unwrap());
+        assert!(names(&index, "dog").is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{File, FileKind};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// Units of `modified:` in seconds.
+const AGE_UNITS: &[(&str, u64)] = &[
+    ("min", MINUTE),
+    ("mo", 30 * DAY),
+    ("s", 1),
+    ("h", HOUR),
+    ("d", DAY),
+    ("w", 7 * DAY),
+    ("y", 365 * DAY),
+];
+
+/// Units of `size:` in bytes.
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("kb", 1 << 10),
+    ("mb", 1 << 20),
+    ("gb", 1 << 30),
+    ("k", 1 << 10),
+    ("m", 1 << 20),
+    ("g", 1 << 30),
+    ("b", 1),
+];
+
+/// Search qualifiers filtering files by their metadata.
+///
+/// * `ext:pdf` or `ext:jpg,png`: the file has one of the extensions
+/// * `type:dir`, `type:file`, `type:link` or a MIME type like `type:image`
+///   or `type:application/pdf`, also separated by commas
+/// * `modified:<7d`: modified less than 7 days ago, `modified:>1y` more
+///   than a year ago, in `s`, `min`, `h`, `d`, `w`, `mo` or `y`
+/// * `size:>10M` or `size:<1k`, in bytes or `k`, `M` or `G`
+///
+/// A file must match every qualifier.  Qualifiers that can't be parsed,
+/// as while one is being typed, are searched for as text.
+#[derive(Debug, Default, PartialEq)]
+pub struct Filter {
+    qualifiers: Vec<Qualifier>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Qualifier {
+    /// Lowercase extensions without the dot.
+    Ext(Vec<String>),
+    Type(Vec<String>),
+    /// Seconds since the file was modified.
+    Age(Bound),
+    /// Size in bytes.
+    Size(Bound),
+}
+
+/// A `<` or `>` comparison.
+#[derive(Debug, PartialEq)]
+struct Bound {
+    less: bool,
+    value: u64,
+}
+
+impl Bound {
+    /// Parse `<` or `>`, a number and a unit.
+    fn parse(text: &str, units: &[(&str, u64)]) -> Option<Self> {
+        let (less, text) = match text.chars().next()? {
+            '<' => (true, &text[1..]),
+            '>' => (false, &text[1..]),
+            _ => return None,
+        };
+        let text = text.to_lowercase();
+        let split = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let scale = if unit.is_empty() {
+            units.iter().find(|(_, scale)| *scale == 1)?.1
+        } else {
+            units.iter().find(|(name, _)| *name == unit)?.1
+        };
+        Some(Bound {
+            less,
+            value: (number * scale as f64) as u64,
+        })
+    }
+
+    fn matches(&self, value: u64) -> bool {
+        if self.less {
+            value < self.value
+        } else {
+            value > self.value
+        }
+    }
+}
+
+/// Split a qualifier's comma separated values.
+fn values(text: &str) -> Option<Vec<String>> {
+    let values: Vec<String> = text
+        .split(',')
+        .filter(|value| !value.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+impl Qualifier {
+    fn parse(token: &str) -> Option<Self> {
+        let (name, value) = token.split_at(token.find(':')?);
+        let value = &value[1..];
+        match name {
+            "ext" => values(value).map(|exts| {
+                let exts = exts.iter().map(|ext| ext.trim_start_matches('.'));
+                Qualifier::Ext(exts.map(str::to_owned).collect())
+            }),
+            "type" => values(value).map(Qualifier::Type),
+            "modified" => Bound::parse(value, AGE_UNITS).map(Qualifier::Age),
+            "size" => Bound::parse(value, SIZE_UNITS).map(Qualifier::Size),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, file: &File, now: u64) -> bool {
+        match self {
+            Qualifier::Ext(exts) => match file.path.extension() {
+                Some(ext) => {
+                    let ext = ext.to_string_lossy().to_lowercase();
+                    exts.contains(&ext)
+                }
+                None => false,
+            },
+            Qualifier::Type(types) => {
+                types.iter().any(|file_type| match file_type.as_str() {
+                    "dir" => file.kind == FileKind::Dir,
+                    "file" => file.kind == FileKind::File,
+                    "link" => file.kind == FileKind::Symlink,
+                    mime_type => match &file.mime {
+                        Some(mime) if mime_type.contains('/') => {
+                            mime == mime_type
+                        }
+                        Some(mime) => matches!(
+                            mime.strip_prefix(mime_type),
+                            Some(rest) if rest.starts_with('/')
+                        ),
+                        None => false,
+                    },
+                })
+            }
+            Qualifier::Age(bound) => {
+                bound.matches(now.saturating_sub(file.modified))
+            }
+            Qualifier::Size(bound) => {
+                file.kind != FileKind::Dir && bound.matches(file.size)
+            }
+        }
+    }
+}
+
+impl Filter {
+    /// Take the qualifiers out of a search, returning them and the rest
+    /// of the search.
+    pub fn parse(input: &str) -> (Self, String) {
+        let mut qualifiers = Vec::new();
+        let mut rest = Vec::new();
+        for token in input.split_whitespace() {
+            match Qualifier::parse(token) {
+                Some(qualifier) => qualifiers.push(qualifier),
+                None => rest.push(token),
+            }
+        }
+        (Filter { qualifiers }, rest.join(" "))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.qualifiers.is_empty()
+    }
+
+    /// Whether a file matches the qualifiers, `now` being the current
+    /// time in seconds since the Unix epoch.
+    pub fn matches(&self, file: &File, now: u64) -> bool {
+        self.qualifiers
+            .iter()
+            .all(|qualifier| qualifier.matches(file, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(path: &str, kind: FileKind, size: u64, modified: u64) -> File {
+        let path = PathBuf::from(path);
+        File {
+            name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            rel: path.to_string_lossy().into_owned(),
+            mime: match path.extension().and_then(|ext| ext.to_str()) {
+                Some("pdf") => Some("application/pdf".to_owned()),
+                Some("png") => Some("image/png".to_owned()),
+                _ => None,
+            },
+            path,
+            kind,
+            size,
+            modified,
+        }
+    }
+
+    fn matching(search: &str, files: &[File]) -> Vec<String> {
+        let (filter, _) = Filter::parse(search);
+        files
+            .iter()
+            .filter(|file| filter.matches(file, 100 * DAY))
+            .map(|file| file.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn parse_qualifiers() {
+        let (filter, rest) = Filter::parse("report ext:pdf,.MD size:>1.5k");
+        assert_eq!(rest, "report");
+        assert_eq!(
+            filter.qualifiers,
+            vec![
+                Qualifier::Ext(vec!["pdf".to_owned(), "md".to_owned()]),
+                Qualifier::Size(Bound {
+                    less: false,
+                    value: 1536
+                }),
+            ]
+        );
+        let (filter, rest) = Filter::parse("size:> modified:<3mo ext:");
+        assert_eq!(rest, "size:> ext:");
+        assert_eq!(
+            filter.qualifiers,
+            vec![Qualifier::Age(Bound {
+                less: true,
+                value: 90 * DAY
+            })]
+        );
+        assert!(Filter::parse("to:do").0.is_empty());
+    }
+
+    #[test]
+    fn filter_files() {
+        let files = vec![
+            file("docs", FileKind::Dir, 4096, 99 * DAY),
+            file("docs/report.pdf", FileKind::File, 3 << 20, 99 * DAY),
+            file("old.PDF", FileKind::File, 1 << 10, 10 * DAY),
+            file("shot.png", FileKind::File, 20 << 20, 95 * DAY),
+            file("latest", FileKind::Symlink, 0, 100 * DAY),
+        ];
+        assert_eq!(matching("ext:pdf", &files), vec!["report.pdf", "old.PDF"]);
+        assert_eq!(matching("type:dir,link", &files), vec!["docs", "latest"]);
+        assert_eq!(matching("type:image", &files), vec!["shot.png"]);
+        assert_eq!(
+            matching("type:application/pdf", &files),
+            vec!["report.pdf"]
+        );
+        assert_eq!(
+            matching("modified:<1w type:file", &files),
+            vec!["report.pdf", "shot.png"]
+        );
+        assert_eq!(matching("modified:>30d", &files), vec!["old.PDF"]);
+        assert_eq!(
+            matching("size:>2M", &files),
+            vec!["report.pdf", "shot.png"]
+        );
+        assert_eq!(matching("size:>2M size:<10M", &files), vec!["report.pdf"]);
+    }
+}
@@ -14,41 +14,175 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
+/// Search the text inside files
+pub mod contents;
+/// Filter searches by file metadata
+mod filter;
+/// Find the files to index
+mod walk;
+
+use self::filter::Filter;
+use self::walk::Walker;
+use super::apps::{mime, App};
 use super::ListItem;
-use super::Plugin;
+use super::{frecent_items, FrecentItem, Plugin, PluginOptions, ScoredItem};
+use crate::cancel::CancelToken;
 use crate::config::Config;
+use crate::event::Event;
 use crate::frecency_db::*;
-use crate::run::run_bg;
-use anyhow::Context as _;
-use anyhow::{Error, Result};
-use log::{debug, trace};
+use crate::matcher::{index_ranges, MatcherKind};
+use crate::run::xdg_open;
+use crate::{Action, ActionResult, Badge, HOME_PATH};
+use anyhow::{anyhow, Error, Result};
+use log::{debug, error, trace, warn};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
+use std::collections::HashMap;
+use std::fs;
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
-use std::process::Command;
-use std::sync::Mutex;
-use walkdir::{DirEntry, WalkDir};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct Files {
+    index: Arc<FileIndex>,
+    watch: bool,
+    rescan_interval: Duration,
+}
+
+/// The indexed files, shared with the file system watcher and the
+/// contents plugin.
+pub struct FileIndex {
     db: Mutex<FilesDB>,
+    walker: Walker,
+    listeners: Mutex<Vec<Arc<dyn FileListener>>>,
+}
+
+/// Told about the files the index finds, so other plugins can use them
+/// without walking and watching the roots again.
+trait FileListener: Send + Sync {
+    /// The roots were walked again and these are all the files in them.
+    fn rescanned(&self, files: &[File]) -> Result<Vec<Error>>;
+    /// These files were created or modified.
+    ///
+    /// Returns whether the listener changed.
+    fn updated(&self, files: &[File]) -> Result<bool>;
+    /// A file or directory and the files under it were removed.
+    ///
+    /// Returns whether the listener changed.
+    fn removed(&self, path: &Path) -> Result<bool>;
+}
+
+/// Options of the files plugin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FilesOptions {
+    /// Directories to index.
+    pub roots: Vec<String>,
+    /// How many directories deep to index, unlimited if `None`.
+    pub max_depth: Option<usize>,
+    /// Index the contents of symlinked directories.
+    pub follow_symlinks: bool,
+    /// Only index files matching one of these globs, if there are any.
+    pub include: Vec<String>,
+    /// Don't index files matching these globs.
+    pub exclude: Vec<String>,
+    /// Don't index files listed in `.gitignore`, `.ignore` and git's other
+    /// exclude files.
+    pub gitignore: bool,
+    /// Index files starting with a dot.
+    pub hidden: bool,
+    /// Most files to index.
+    pub max_items: usize,
+    /// Watch the roots to index files as they're created and removed.
+    ///
+    /// Off by default since watching a whole home directory takes an
+    /// inotify watch for every directory in it.
+    pub watch: bool,
+    /// Minutes between rescans when the roots aren't watched.
+    pub rescan_interval: u64,
+}
+
+impl Default for FilesOptions {
+    fn default() -> Self {
+        FilesOptions {
+            roots: vec!["~".into()],
+            max_depth: Some(6),
+            follow_symlinks: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            gitignore: true,
+            hidden: false,
+            max_items: 50_000,
+            watch: false,
+            rescan_interval: 10,
+        }
+    }
 }
 
 impl Files {
-    pub fn init(config: &Config) -> Result<Self> {
-        let db_path = config.data_dir.join("files.db");
+    fn get_file(&self, id: u64) -> Result<File> {
+        Ok(self
+            .index
+            .db
+            .lock()
+            .unwrap()
+            .get_by_id(id)?
+            .ok_or_else(|| anyhow!("No file with id {}", id))?
+            .item)
+    }
 
-        let db = Mutex::new(FilesDB::new(&db_path)?);
-        Ok(Files { db })
+    /// Load the plugin, sharing the index in `loaded` if the contents
+    /// plugin loaded it first.
+    pub fn init(
+        config: &Config,
+        options: &PluginOptions<FilesOptions>,
+        loaded: &mut Option<Arc<FileIndex>>,
+    ) -> Result<(Self, Vec<Error>)> {
+        let (index, errors) = FileIndex::shared(config, options, loaded)?;
+        Ok((
+            Files {
+                index,
+                watch: options.plugin.watch,
+                rescan_interval: Duration::from_secs(
+                    options.plugin.rescan_interval.max(1) * 60,
+                ),
+            },
+            errors,
+        ))
     }
 }
 
 impl Plugin for Files {
-    fn matcher(&self, _config: &Config, input: &str) -> bool {
-        match input.get(0..1) {
-            Some(":") => true,
-            _ => false,
+    fn name(&self) -> &str {
+        "files"
+    }
+
+    fn default_prefix(&self) -> Option<&str> {
+        Some(":")
+    }
+
+    fn blended_search(
+        &self,
+        _: &Config,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ScoredItem>> {
+        if cancel.is_cancelled() {
+            return Ok(Vec::new());
         }
+        let db = self.index.db.lock().unwrap();
+        Ok(search_files(&db, input, num_items)?
+            .into_iter()
+            .map(|cont| ScoredItem {
+                score: db.normalize_score(cont.score),
+                item: ListItem::from(cont),
+            })
+            .collect())
     }
 
     fn search(
@@ -56,83 +190,477 @@ impl Plugin for Files {
         _: &Config,
         input: &str,
         num_items: usize,
+        cancel: &CancelToken,
     ) -> Result<Vec<crate::ListItem>> {
         trace!("Files search {:?} {:?}", input, num_items);
-        let list: Vec<_> = self
-            .db
-            .lock()
-            .unwrap()
-            .get_ranked_list(&input[1..], Some(num_items))?
+        if cancel.is_cancelled() {
+            return Ok(Vec::new());
+        }
+        let db = self.index.db.lock().unwrap();
+        Ok(search_files(&db, input, num_items)?
             .into_iter()
             .map(ListItem::from)
-            .collect();
-        Ok(list)
+            .collect())
     }
 
-    fn run(&mut self, _config: &Config, id: u64) -> Result<()> {
-        let mut db = self.db.lock().unwrap();
-        let cont = db.get_by_id(id)?.unwrap();
-        cont.item.open()?;
-        db.update_score(cont.id)?;
+    fn run(&self, _config: &Config, id: u64) -> Result<()> {
+        self.get_file(id)?.open()?;
+        self.index.db.lock().unwrap().update_score(id)?;
         Ok(())
     }
 
-    fn reload(&mut self, _config: &Config) -> Result<Vec<Error>> {
-        let (entries, errors): (Vec<_>, Vec<_>) =
-            WalkDir::new("/home/zethra/Documents")
-                .into_iter()
-                .filter_entry(|e| !is_hidden(e))
-                .partition(Result::is_ok);
-        let errors: Vec<_> = errors
-            .into_iter()
-            .map(Result::unwrap_err)
-            .map(|e| Error::new(e).context("Error indexing files"))
-            .collect();
-        let files: Vec<_> = entries
-            .into_iter()
-            .map(Result::unwrap)
-            .map(|e| File {
-                name: (*e.file_name().to_string_lossy()).to_owned(),
-                path: e.path().to_owned(),
-            })
-            .collect();
+    fn top_items(
+        &self,
+        _: &Config,
+        num_items: usize,
+    ) -> Result<Vec<FrecentItem>> {
+        frecent_items(&*self.index.db.lock().unwrap(), num_items)
+    }
+
+    fn actions(&self, _: &Config, id: u64) -> Result<Vec<Action>> {
+        let path = self.get_file(id)?.path;
+        let mut actions = vec![
+            Action::new("open_folder", "Open containing folder"),
+            Action::new("copy_path", "Copy path"),
+        ];
+        if let Ok(mime_type) = mime::file_mime_type(&path) {
+            actions.extend(mime::apps_for_mime_type(&mime_type).iter().map(
+                |app| {
+                    Action::new(
+                        format!("open_with:{}", app.path.display()),
+                        format!("Open with {}", app.name),
+                    )
+                },
+            ));
+        }
+        Ok(actions)
+    }
 
+    fn run_action(
+        &self,
+        _: &Config,
+        id: u64,
+        action: &str,
+    ) -> Result<ActionResult> {
+        let file = self.get_file(id)?;
+        match action {
+            "open_folder" => {
+                xdg_open(file.path.parent().unwrap_or(&file.path))?
+            }
+            "copy_path" => {
+                return Ok(ActionResult::Copy(
+                    file.path.to_string_lossy().into_owned(),
+                ))
+            }
+            _ => match action.strip_prefix("open_with:") {
+                Some(desktop_file) => App::parse_desktop_file(desktop_file)?
+                    .ok_or_else(|| anyhow!("{} is hidden", desktop_file))?
+                    .open_file(&None, &file.path)?,
+                None => return Err(anyhow!("Unknown action {}", action)),
+            },
+        }
+        self.index.db.lock().unwrap().update_score(id)?;
+        Ok(ActionResult::Hide)
+    }
+
+    fn toggle_pin(&self, _: &Config, id: u64) -> Result<bool> {
+        self.index.db.lock().unwrap().toggle_pinned(id)
+    }
+
+    fn add_alias(&self, _: &Config, alias: &str, id: u64) -> Result<()> {
+        self.index.db.lock().unwrap().add_alias(alias, id)
+    }
+
+    fn reload(&self, config: &Config) -> Result<Vec<Error>> {
+        self.index.rescan(&config.file_options.aliases)
+    }
+
+    fn register_event_handlers(
+        &self,
+        config: &Config,
+        event_tx: Sender<Event>,
+    ) {
+        self.index
+            .watch(config, self.watch, self.rescan_interval, event_tx);
+    }
+}
+
+impl FileIndex {
+    fn new(
+        config: &Config,
+        options: &PluginOptions<FilesOptions>,
+    ) -> Result<(Self, Vec<Error>)> {
+        let db_path = config.data_dir.join("files.db");
+        let (walker, errors) = Walker::new(&options.plugin);
+        if walker.roots().is_empty() {
+            warn!("The files plugin has no roots so no files will be found");
+        }
+
+        let mut db = FilesDB::new(&db_path)?;
+        db.set_config_aliases(&config.file_options.aliases)?;
+        db.set_matchers(options.matchers_or(&[MatcherKind::Path]));
+        if let Some(typo_fallback) = options.typo_fallback {
+            db.set_typo_fallback(typo_fallback);
+        }
+        Ok((
+            FileIndex {
+                db: Mutex::new(db),
+                walker,
+                listeners: Mutex::new(Vec::new()),
+            },
+            errors,
+        ))
+    }
+
+    /// Get the index in `loaded`, loading it if it hasn't been.
+    ///
+    /// The errors of loading it are only returned the first time.
+    fn shared(
+        config: &Config,
+        options: &PluginOptions<FilesOptions>,
+        loaded: &mut Option<Arc<FileIndex>>,
+    ) -> Result<(Arc<Self>, Vec<Error>)> {
+        if let Some(index) = loaded {
+            return Ok((index.clone(), Vec::new()));
+        }
+        let (index, errors) = FileIndex::new(config, options)?;
+        let index = Arc::new(index);
+        *loaded = Some(index.clone());
+        Ok((index, errors))
+    }
+
+    /// Tell a listener about the files found from now on.
+    fn listen(&self, listener: Arc<dyn FileListener>) {
+        self.listeners
+            .lock()
+            .expect("Files Mutex poisoned")
+            .push(listener);
+    }
+
+    fn listeners(&self) -> Vec<Arc<dyn FileListener>> {
+        self.listeners.lock().expect("Files Mutex poisoned").clone()
+    }
+
+    /// Watch the roots if `watch` is set, or rescan them every
+    /// `rescan_interval`, keeping the index and its listeners up to date.
+    fn watch(
+        self: &Arc<Self>,
+        config: &Config,
+        watch: bool,
+        rescan_interval: Duration,
+        event_tx: Sender<Event>,
+    ) {
+        let aliases = config.file_options.aliases.clone();
+        let aliases_for_rescan = aliases.clone();
+        let index = self.clone();
+        let rescan_index = self.clone();
+        let rescan = move || rescan_index.rescan(&aliases_for_rescan);
+        if !watch {
+            rescan_periodically(rescan_interval, event_tx, rescan);
+            return;
+        }
+        watch_roots(
+            self.walker.roots(),
+            rescan_interval,
+            event_tx,
+            move |event| index.apply(event, &aliases),
+            rescan,
+        );
+    }
+
+    /// Walk the roots and replace the indexed files.
+    fn rescan(&self, aliases: &HashMap<String, String>) -> Result<Vec<Error>> {
+        let (files, mut errors) = self.walker.walk();
         debug!("Found {} files", files.len());
-        // debug!("{:#?}", files);
-        self.db.lock().unwrap().merge_new_entries(&files)?;
-        debug!("Done writing");
+        let new = NewEntries::new(files.clone(), None)?;
+        {
+            let mut db = self.db.lock().expect("Files Mutex poisoned");
+            db.swap_entries(new)?;
+            db.set_config_aliases(aliases)?;
+        }
+        for listener in self.listeners() {
+            errors.extend(listener.rescanned(&files)?);
+        }
         Ok(errors)
     }
+
+    /// Update the index for a file system event, returning whether it
+    /// changed.
+    fn apply(
+        &self,
+        event: DebouncedEvent,
+        aliases: &HashMap<String, String>,
+    ) -> Result<bool> {
+        let changed = match event {
+            DebouncedEvent::Create(path) => self.add_path(&path)?,
+            DebouncedEvent::Remove(path) => self.remove_path(&path)?,
+            DebouncedEvent::Rename(from, to) => {
+                let removed = self.remove_path(&from)?;
+                self.add_path(&to)? || removed
+            }
+            DebouncedEvent::Write(path)
+                if self.walker.is_ignore_file(&path) =>
+            {
+                for e in self.rescan(aliases)? {
+                    warn!("{:?}", e);
+                }
+                return Ok(true);
+            }
+            DebouncedEvent::Write(path) if path.is_file() => {
+                self.add_path(&path)?
+            }
+            DebouncedEvent::Rescan => {
+                for e in self.rescan(aliases)? {
+                    warn!("{:?}", e);
+                }
+                return Ok(true);
+            }
+            DebouncedEvent::Error(e, path) => {
+                return Err(Error::new(e)
+                    .context(format!("Error watching files {:?}", path)))
+            }
+            _ => false,
+        };
+        if changed {
+            let mut db = self.db.lock().expect("Files Mutex poisoned");
+            db.set_config_aliases(aliases)?;
+        }
+        Ok(changed)
+    }
+
+    /// Index a created file or directory and the files under it.
+    fn add_path(&self, path: &Path) -> Result<bool> {
+        if self.walker.is_ignore_file(path) {
+            return Ok(false);
+        }
+        let (mut files, errors) = self.walker.walk_path(path);
+        for e in errors {
+            warn!("{:?}", e);
+        }
+        {
+            let mut db = self.db.lock().expect("Files Mutex poisoned");
+            let room = self.walker.max_items().saturating_sub(db.len());
+            files.truncate(room);
+            if files.is_empty() {
+                return Ok(false);
+            }
+            debug!(
+                "Indexing {} new files under {}",
+                files.len(),
+                path.display()
+            );
+            db.upsert_entries(files.iter().cloned())?;
+        }
+        for listener in self.listeners() {
+            listener.updated(&files)?;
+        }
+        Ok(true)
+    }
+
+    /// Remove a deleted file or directory and the files under it.
+    fn remove_path(&self, path: &Path) -> Result<bool> {
+        let mut changed = {
+            let mut db = self.db.lock().expect("Files Mutex poisoned");
+            let len = db.len();
+            db.remove_entries(|file| file.path.starts_with(path))?;
+            db.len() != len
+        };
+        for listener in self.listeners() {
+            changed |= listener.removed(path)?;
+        }
+        Ok(changed)
+    }
+}
+
+/// Watch roots for changes, calling `apply` with each change on a
+/// background thread and sending a refresh if it returns true.
+///
+/// If the roots can't be watched, `rescan` is called every
+/// `rescan_interval` instead.
+fn watch_roots(
+    roots: &[PathBuf],
+    rescan_interval: Duration,
+    event_tx: Sender<Event>,
+    apply: impl Fn(DebouncedEvent) -> Result<bool> + Send + 'static,
+    rescan: impl Fn() -> Result<Vec<Error>> + Send + 'static,
+) {
+    let (tx, rx) = mpsc::channel();
+    let watched = watcher(tx, Duration::from_secs(2))
+        .map_err(|e| {
+            Error::new(e).context("Error creating file system watcher")
+        })
+        .and_then(|mut watcher| {
+            for root in roots {
+                if !root.exists() {
+                    continue;
+                }
+                watcher.watch(root, RecursiveMode::Recursive).map_err(|e| {
+                    Error::new(e).context(format!(
+                        "Error watching {} for new files",
+                        root.display()
+                    ))
+                })?;
+            }
+            Ok(watcher)
+        });
+    match watched {
+        Ok(watcher) => {
+            std::mem::forget(watcher);
+            thread::spawn(move || {
+                for event in rx {
+                    trace!("File watcher received: {:?}", event);
+                    match apply(event) {
+                        Ok(true) => send_refresh(&event_tx),
+                        Ok(false) => {}
+                        Err(e) => error!("{:?}", e),
+                    }
+                }
+            });
+        }
+        // Usually the inotify watch limit, which big homes run into
+        Err(e) => {
+            warn!("{:?}", e.context("Rescanning files periodically instead"));
+            rescan_periodically(rescan_interval, event_tx, rescan);
+        }
+    }
+}
+
+/// Call `rescan` every `rescan_interval` on a background thread, sending
+/// a refresh after each rescan.
+fn rescan_periodically(
+    rescan_interval: Duration,
+    event_tx: Sender<Event>,
+    rescan: impl Fn() -> Result<Vec<Error>> + Send + 'static,
+) {
+    thread::spawn(move || loop {
+        thread::sleep(rescan_interval);
+        match rescan() {
+            Ok(errors) => {
+                for e in errors {
+                    warn!("{:?}", e);
+                }
+                send_refresh(&event_tx);
+            }
+            Err(e) => error!("{:?}", e),
+        }
+    });
+}
+
+/// Search the files, filtering them by the qualifiers of the search.
+///
+/// A search of only qualifiers lists the files matching them, most used
+/// first.
+fn search_files(
+    db: &FilesDB,
+    input: &str,
+    num_items: usize,
+) -> Result<Vec<Container<File>>> {
+    let (filter, search) = Filter::parse(input);
+    if filter.is_empty() {
+        return db.get_ranked_list(input, Some(num_items));
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    let keep = |file: &File| filter.matches(file, now);
+    if search.is_empty() {
+        Ok(db.get_top_where(num_items, keep))
+    } else {
+        db.get_ranked_list_where(&search, Some(num_items), keep)
+    }
 }
 
-fn is_hidden(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s.starts_with("."))
-        .unwrap_or(false)
+fn send_refresh(event_tx: &Sender<Event>) {
+    if let Err(e) = event_tx.send(Event::Refresh) {
+        error!("Error sending event to ui: {:?}", e);
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq)]
 struct File {
     name: String,
     path: PathBuf,
+    /// The path relative to the root it was found in, which is what
+    /// searches are matched against.
+    #[serde(default)]
+    rel: String,
+    #[serde(default)]
+    kind: FileKind,
+    /// Size in bytes.
+    #[serde(default)]
+    size: u64,
+    /// When the file was last modified, in seconds since the Unix epoch.
+    #[serde(default)]
+    modified: u64,
+    /// The MIME type guessed from the file's name.
+    #[serde(default)]
+    mime: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+enum FileKind {
+    #[default]
+    File,
+    Dir,
+    Symlink,
 }
 
 impl File {
+    /// Read the metadata of a file found at `rel` in a root.
+    fn new(path: PathBuf, rel: String, is_dir: bool, is_symlink: bool) -> Self {
+        let metadata =
+            fs::metadata(&path).or_else(|_| fs::symlink_metadata(&path));
+        let (size, modified) = match metadata {
+            Ok(metadata) => (
+                metadata.len(),
+                metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |time| time.as_secs()),
+            ),
+            Err(_) => (0, 0),
+        };
+        let kind = if is_dir {
+            FileKind::Dir
+        } else if is_symlink {
+            FileKind::Symlink
+        } else {
+            FileKind::File
+        };
+        let mime = match kind {
+            FileKind::Dir => None,
+            _ => mime::guess_mime_type(&path).map(str::to_owned),
+        };
+        File {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path,
+            rel,
+            kind,
+            size,
+            modified,
+            mime,
+        }
+    }
+
     pub fn open(&self) -> Result<()> {
-        let mut command = Command::new("xdg-open");
-        command.arg(self.path.as_path().as_os_str());
-        let _ = run_bg(command).with_context(|| {
-            format!("Error opening file {}", self.path.display())
-        })?;
-        Ok(())
+        xdg_open(&self.path)
     }
 }
 
 impl DBItem for File {
     fn get_sort_string(&self) -> &str {
-        self.name.as_str()
+        // Files indexed before relative paths were stored
+        if self.rel.is_empty() {
+            self.name.as_str()
+        } else {
+            self.rel.as_str()
+        }
     }
 }
 
@@ -152,10 +680,105 @@ type FilesDB = FrecencyDB<File>;
 
 impl From<Container<File>> for ListItem {
     fn from(cont: Container<File>) -> Self {
+        let file = &cont.item;
+        // The name ends the sort string, so only highlight the matches in it
+        let offset =
+            file.get_sort_string().chars().count() - file.name.chars().count();
+        let indices: Vec<usize> = cont
+            .indices
+            .iter()
+            .filter(|&&idx| idx >= offset)
+            .map(|&idx| idx - offset)
+            .collect();
+        let subtitle = match Path::new(&file.rel).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => {
+                dir.display().to_string()
+            }
+            // At the top of its root, so show the root
+            _ => {
+                let dir = file.path.parent().unwrap_or(&file.path);
+                match dir.strip_prefix(&*HOME_PATH) {
+                    Ok(rel) => Path::new("~").join(rel).display().to_string(),
+                    Err(_) => dir.display().to_string(),
+                }
+            }
+        };
         Self {
-            name: cont.item.name.clone(),
+            name: file.name.clone(),
             icon: "".to_owned(),
             id: cont.id,
+            matches: index_ranges(&indices),
+            subtitle,
+            source: String::new(),
+            badges: if cont.pinned {
+                vec![Badge::Pinned]
+            } else {
+                Vec::new()
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    /// Whether the index holds exactly these file names.
+    fn indexes(index: &FileIndex, names: &[&str]) -> bool {
+        let db = index.db.lock().unwrap();
+        db.len() == names.len()
+            && names.iter().all(|name| {
+                db.get_ranked_list(name, None)
+                    .unwrap()
+                    .iter()
+                    .any(|cont| cont.item.name == *name)
+            })
+    }
+
+    #[test]
+    fn watcher_events() {
+        let dir = TestDir::new("files-watch");
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs/report.pdf"), "").unwrap();
+        fs::write(root.join(".ignore"), "*.log\n").unwrap();
+
+        let options = FilesOptions {
+            roots: vec![root.to_string_lossy().into_owned()],
+            ..FilesOptions::default()
+        };
+        let index = FileIndex {
+            db: Mutex::new(FilesDB::new(dir.join("files.db")).unwrap()),
+            walker: Walker::new(&options).0,
+            listeners: Mutex::new(Vec::new()),
+        };
+        let aliases = HashMap::new();
+        assert!(index.rescan(&aliases).unwrap().is_empty());
+        assert!(indexes(&index, &["docs", "report.pdf"]));
+
+        fs::create_dir_all(root.join("new/.cache")).unwrap();
+        fs::write(root.join("new/notes.md"), "").unwrap();
+        fs::write(root.join("new/debug.log"), "").unwrap();
+        let event = DebouncedEvent::Create(root.join("new"));
+        assert!(index.apply(event, &aliases).unwrap());
+        assert!(indexes(&index, &["docs", "new", "notes.md", "report.pdf"]));
+
+        let event = DebouncedEvent::Create(root.join("new/debug.log"));
+        assert!(!index.apply(event, &aliases).unwrap());
+
+        fs::rename(root.join("docs"), root.join("papers")).unwrap();
+        let event =
+            DebouncedEvent::Rename(root.join("docs"), root.join("papers"));
+        assert!(index.apply(event, &aliases).unwrap());
+        assert!(indexes(
+            &index,
+            &["new", "notes.md", "papers", "report.pdf"]
+        ));
+
+        fs::remove_dir_all(root.join("new")).unwrap();
+        let event = DebouncedEvent::Remove(root.join("new"));
+        assert!(index.apply(event, &aliases).unwrap());
+        assert!(indexes(&index, &["papers", "report.pdf"]));
+    }
+}
@@ -0,0 +1,392 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{File, FilesOptions};
+use anyhow::Error;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{DirEntry, WalkBuilder};
+use log::{debug, warn};
+use std::path::{Path, PathBuf};
+
+/// Files listing paths to leave out, read when `gitignore` is on.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".git/info/exclude"];
+
+/// Walks the directories the files plugin indexes.
+#[derive(Debug)]
+pub struct Walker {
+    roots: Vec<PathBuf>,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    /// Matches paths relative to their root.
+    include: Override,
+    /// Matches paths relative to their root.
+    exclude: Override,
+    gitignore: bool,
+    hidden: bool,
+    max_items: usize,
+}
+
+/// Compile the globs of an option, reporting the invalid ones.
+///
+/// The globs ignore what they match if `ignore` is set, otherwise they
+/// whitelist it.
+fn globs(
+    patterns: &[String],
+    ignore: bool,
+    option: &str,
+    errors: &mut Vec<Error>,
+) -> Override {
+    let mut builder = OverrideBuilder::new("");
+    for pattern in patterns {
+        let glob = if ignore {
+            format!("!{}", pattern)
+        } else {
+            pattern.clone()
+        };
+        if let Err(e) = builder.add(&glob) {
+            errors.push(Error::new(e).context(format!(
+                "Invalid glob in the `{}` option of plugin `files`",
+                option
+            )));
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        errors.push(Error::new(e).context(format!(
+            "Invalid globs in the `{}` option of plugin `files`",
+            option
+        )));
+        Override::empty()
+    })
+}
+
+/// Whether an entry is a directory, or a symlink to one when following
+/// symlinks.
+fn is_dir(entry: &DirEntry) -> bool {
+    matches!(entry.file_type(), Some(file_type) if file_type.is_dir())
+}
+
+/// Get a path relative to a directory with `/` between its components.
+fn relative(dir: &Path, path: &Path) -> String {
+    let rel = path.strip_prefix(dir).unwrap_or(path);
+    rel.components()
+        .map(|comp| comp.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Drop roots listed twice or inside another root, whose files would
+/// otherwise be found twice.
+fn outer_roots(roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut outer: Vec<PathBuf> = Vec::with_capacity(roots.len());
+    for root in &roots {
+        let nested = roots
+            .iter()
+            .any(|other| other != root && root.starts_with(other));
+        if nested || outer.contains(root) {
+            debug!("Skipping root {} inside another root", root.display());
+        } else {
+            outer.push(root.clone());
+        }
+    }
+    outer
+}
+
+impl Walker {
+    /// Create a walker from the plugin's options, reporting invalid
+    /// roots and globs.
+    pub fn new(options: &FilesOptions) -> (Self, Vec<Error>) {
+        let mut errors = Vec::new();
+        let roots: Vec<PathBuf> = options
+            .roots
+            .iter()
+            .filter_map(|root| match shellexpand::full(root) {
+                Ok(path) => Some(PathBuf::from(&*path)),
+                Err(e) => {
+                    errors.push(Error::new(e).context(format!(
+                        "Invalid path `{}` in the `roots` option of plugin \
+                         `files`",
+                        root
+                    )));
+                    None
+                }
+            })
+            .collect();
+        let roots = outer_roots(roots);
+        let include = globs(&options.include, false, "include", &mut errors);
+        let exclude = globs(&options.exclude, true, "exclude", &mut errors);
+        (
+            Walker {
+                roots,
+                max_depth: options.max_depth,
+                follow_symlinks: options.follow_symlinks,
+                include,
+                exclude,
+                gitignore: options.gitignore,
+                hidden: options.hidden,
+                max_items: options.max_items,
+            },
+            errors,
+        )
+    }
+
+    /// The directories the walker indexes.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Find the files and directories under the roots.
+    pub fn walk(&self) -> (Vec<File>, Vec<Error>) {
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        for root in &self.roots {
+            if self.walk_root(root, None, &mut files, &mut errors) {
+                warn!(
+                    "Stopped indexing files after the limit of {} items",
+                    self.max_items
+                );
+                break;
+            }
+        }
+        debug!("Walked {} files", files.len());
+        (files, errors)
+    }
+
+    /// Find a path and the files under it, if they'd be found by `walk`.
+    ///
+    /// Used to index a file or directory as soon as it's created.
+    pub fn walk_path(&self, path: &Path) -> (Vec<File>, Vec<Error>) {
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        if let Some(root) = self.root_of(path) {
+            if root != path {
+                self.walk_root(root, Some(path), &mut files, &mut errors);
+            }
+        }
+        (files, errors)
+    }
+
+    /// The innermost root a path is under.
+    pub fn root_of(&self, path: &Path) -> Option<&Path> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .map(PathBuf::as_path)
+    }
+
+    /// Whether a path is an ignore file, so changing it changes what's
+    /// indexed.
+    pub fn is_ignore_file(&self, path: &Path) -> bool {
+        self.gitignore && IGNORE_FILES.iter().any(|name| path.ends_with(name))
+    }
+
+    /// The most files to index.
+    pub fn max_items(&self) -> usize {
+        self.max_items
+    }
+
+    /// Walk a root, only going down to `only` and the files under it if
+    /// it's set.
+    ///
+    /// Returns whether `max_items` was reached.
+    fn walk_root(
+        &self,
+        root: &Path,
+        only: Option<&Path>,
+        files: &mut Vec<File>,
+        errors: &mut Vec<Error>,
+    ) -> bool {
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .max_depth(self.max_depth)
+            .follow_links(self.follow_symlinks)
+            .hidden(!self.hidden)
+            .parents(self.gitignore)
+            .ignore(self.gitignore)
+            .git_ignore(self.gitignore)
+            .git_exclude(self.gitignore)
+            .git_global(self.gitignore)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b));
+        let exclude = self.exclude.clone();
+        let root_path = root.to_owned();
+        let only_path = only.map(Path::to_owned);
+        builder.filter_entry(move |entry| {
+            let path = entry.path();
+            if let Some(only) = &only_path {
+                if !path.starts_with(only) && !only.starts_with(path) {
+                    return false;
+                }
+            }
+            !exclude
+                .matched(relative(&root_path, path), is_dir(entry))
+                .is_ignore()
+        });
+
+        for entry in builder.build() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // Invalid rules in ignore files and symlink loops
+                Err(e) if !e.is_io() => {
+                    debug!("Skipping: {}", e);
+                    continue;
+                }
+                Err(e) => {
+                    errors.push(Error::new(e).context(format!(
+                        "Error indexing files in {}",
+                        root.display()
+                    )));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            // The root itself and the directories above `only`
+            if entry.depth() == 0
+                || matches!(only, Some(only) if !path.starts_with(only))
+            {
+                continue;
+            }
+            if files.len() >= self.max_items {
+                return true;
+            }
+            let is_dir = is_dir(&entry);
+            let rel = relative(root, path);
+            if self.include.is_empty()
+                || self.include.matched(&rel, is_dir).is_whitelist()
+            {
+                files.push(File::new(
+                    path.to_owned(),
+                    rel,
+                    is_dir,
+                    entry.path_is_symlink(),
+                ));
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::fs;
+
+    fn names(files: &[File]) -> Vec<String> {
+        let mut names: Vec<_> =
+            files.iter().map(|file| file.name.clone()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn walk_options() {
+        let dir = TestDir::new("files-walk");
+        for sub in &["docs/old", "src/target", ".config", ".git/info"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for file in &[
+            "notes.md",
+            "docs/report.pdf",
+            "docs/old/draft.pdf",
+            "src/main.rs",
+            "src/target/out.o",
+            "src/debug.log",
+            "src/keep.log",
+            ".config/settings",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(dir.join(".ignore"), "*.log\n!keep.log\n").unwrap();
+        fs::write(dir.join("src/.gitignore"), "/target\n").unwrap();
+        fs::write(dir.join(".git/info/exclude"), "/notes.md\n").unwrap();
+        let long_name = "a".repeat(100);
+        fs::write(dir.join("docs").join(&long_name), "").unwrap();
+
+        let root = dir.to_string_lossy().into_owned();
+        let options = FilesOptions {
+            roots: vec![root.clone()],
+            ..FilesOptions::default()
+        };
+        let (walker, errors) = Walker::new(&options);
+        assert!(errors.is_empty());
+        let (files, errors) = walker.walk();
+        assert!(errors.is_empty());
+        assert_eq!(
+            names(&files),
+            vec![
+                &long_name,
+                "docs",
+                "draft.pdf",
+                "keep.log",
+                "main.rs",
+                "old",
+                "report.pdf",
+                "src"
+            ]
+        );
+
+        let options = FilesOptions {
+            roots: vec![root.clone()],
+            max_depth: Some(2),
+            include: vec!["*.pdf".to_owned()],
+            // Globs don't backtrack, so this doesn't take ages
+            exclude: vec!["old/".to_owned(), "*a*a*a*a*a*a*a*a*b".to_owned()],
+            ..FilesOptions::default()
+        };
+        let (files, _) = Walker::new(&options).0.walk();
+        assert_eq!(names(&files), vec!["report.pdf"]);
+
+        let options = FilesOptions {
+            roots: vec![root],
+            gitignore: false,
+            hidden: true,
+            max_items: 3,
+            ..FilesOptions::default()
+        };
+        let (files, _) = Walker::new(&options).0.walk();
+        assert_eq!(names(&files), vec![".config", ".git", "settings"]);
+
+        let options = FilesOptions {
+            exclude: vec!["[a".to_owned()],
+            ..FilesOptions::default()
+        };
+        assert_eq!(Walker::new(&options).1.len(), 1);
+    }
+
+    #[test]
+    fn overlapping_roots() {
+        let dir = TestDir::new("files-roots");
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/report.pdf"), "").unwrap();
+        fs::write(dir.join("notes.md"), "").unwrap();
+
+        let root = dir.to_string_lossy().into_owned();
+        let options = FilesOptions {
+            roots: vec![
+                format!("{}/docs/", root),
+                root.clone(),
+                dir.join("docs").to_string_lossy().into_owned(),
+                root,
+            ],
+            ..FilesOptions::default()
+        };
+        let walker = Walker::new(&options).0;
+        assert_eq!(walker.roots(), &[dir.to_path_buf()]);
+        let (files, _) = walker.walk();
+        assert_eq!(names(&files), vec!["docs", "notes.md", "report.pdf"]);
+    }
+}
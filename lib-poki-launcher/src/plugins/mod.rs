@@ -14,19 +14,36 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
-mod apps;
+pub(crate) mod apps;
 mod files;
+/// Typed plugin options
+pub mod options;
+mod script;
 
+use crate::cancel::CancelToken;
 use crate::config::Config;
 use crate::event::Event;
-use crate::ListItem;
+use crate::frecency_db::{Container, DBItem, FrecencyDB};
+use crate::{Action, ActionResult, ListItem};
 use anyhow::{anyhow, Error, Result};
 use log::{info, warn};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
 
-pub fn init_plugins(config: &Config) -> (Vec<Box<dyn Plugin>>, Vec<Error>) {
-    let mut plugins: Vec<Box<dyn Plugin>> = Vec::new();
+pub use self::options::{ModeOptions, PluginOptions};
+
+/// Load the plugins in the configured order.
+///
+/// Returns the plugins that loaded, how each of them is picked to search
+/// and the errors from loading them.
+pub fn init_plugins(
+    config: &Config,
+) -> (Vec<Arc<dyn Plugin>>, Vec<PluginMode>, Vec<Error>) {
+    let mut plugins: Vec<Arc<dyn Plugin>> = Vec::new();
+    let mut mode_options = Vec::new();
     let mut errors = Vec::new();
+    // Shared by the files and contents plugins
+    let mut file_index = None;
     if config.file_options.plugin_load_order.is_empty() {
         warn!(
             "No plugins loading, launcher will do nothing. \
@@ -34,49 +51,262 @@ pub fn init_plugins(config: &Config) -> (Vec<Box<dyn Plugin>>, Vec<Error>) {
         )
     }
     for plugin_name in &config.file_options.plugin_load_order {
-        match plugin_name.as_str() {
-            "apps" => match self::apps::Apps::init(&config) {
-                Ok((apps, errs)) => {
-                    info!("Loading plugin: `apps`");
-                    plugins.push(Box::new(apps));
-                    errors.extend(errs);
-                }
-                Err(e) => {
-                    errors.push(e);
-                }
-            },
-            "files" => match self::files::Files::init(&config) {
-                Ok(apps) => {
-                    info!("Loading plugin: `files`");
-                    plugins.push(Box::new(apps))
-                }
-                Err(e) => {
-                    errors.push(e);
-                }
-            },
-            _ => errors.push(anyhow!("Unknown plugin: `{}`", plugin_name)),
+        match load_plugin(config, plugin_name, &mut file_index) {
+            Ok((plugin, mode, errs)) => {
+                info!("Loading plugin: `{}`", plugin_name);
+                plugins.push(plugin);
+                mode_options.push(mode);
+                errors.extend(errs);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    let modes = plugin_modes(&plugins, &mode_options);
+    (plugins, modes, errors)
+}
+
+/// Read a plugin's options and load it.
+///
+/// `file_index` holds the files index once the files or contents plugin
+/// loads it.
+fn load_plugin(
+    config: &Config,
+    name: &str,
+    file_index: &mut Option<Arc<self::files::FileIndex>>,
+) -> Result<(Arc<dyn Plugin>, ModeOptions, Vec<Error>)> {
+    let plugins = &config.file_options.plugins;
+    match name {
+        "apps" => {
+            let options = PluginOptions::from_config(plugins, name)?;
+            let (apps, errors) = self::apps::Apps::init(config, &options)?;
+            Ok((Arc::new(apps), options.mode, errors))
+        }
+        "files" => {
+            let options = PluginOptions::from_config(plugins, name)?;
+            let (files, errors) =
+                self::files::Files::init(config, &options, file_index)?;
+            Ok((Arc::new(files), options.mode, errors))
+        }
+        "contents" => {
+            let options = PluginOptions::from_config(plugins, name)?;
+            let (contents, errors) = self::files::contents::Contents::init(
+                config, &options, file_index,
+            )?;
+            Ok((Arc::new(contents), options.mode, errors))
         }
+        name => match self::script::Script::init(config, name) {
+            Some(script) => {
+                let (script, mode) = script?;
+                Ok((Arc::new(script), mode, Vec::new()))
+            }
+            None => Err(anyhow!("Unknown plugin: `{}`", name)),
+        },
     }
-    (plugins, errors)
+}
+
+/// How a plugin is picked to search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginMode {
+    /// Searches starting with this only search the plugin.
+    pub prefix: Option<String>,
+    /// Searches without a prefix search the plugin.
+    pub default: bool,
+}
+
+/// Get how each plugin is picked to search from its options.
+///
+/// A plugin's `prefix` option replaces its built in prefix and an empty
+/// prefix removes it.  If no plugin sets the `default` option, the plugins
+/// without a prefix are the default.
+fn plugin_modes(
+    plugins: &[Arc<dyn Plugin>],
+    options: &[ModeOptions],
+) -> Vec<PluginMode> {
+    let any_default = options.iter().any(|mode| mode.default.is_some());
+    plugins
+        .iter()
+        .zip(options)
+        .map(|(plugin, options)| {
+            let prefix = match &options.prefix {
+                Some(prefix) if prefix.is_empty() => None,
+                Some(prefix) => Some(prefix.clone()),
+                None => plugin.default_prefix().map(str::to_owned),
+            };
+            let default = if any_default {
+                options.default.unwrap_or(false)
+            } else {
+                prefix.is_none()
+            };
+            PluginMode { prefix, default }
+        })
+        .collect()
+}
+
+/// An item found by a search with a score comparable between plugins.
+pub struct ScoredItem {
+    pub item: ListItem,
+    /// Score between 0 and 1 where 1 is a perfect match of the most used
+    /// item.
+    pub score: f64,
+}
+
+/// An item ranked only by how often and how recently it was used.
+pub struct FrecentItem {
+    pub item: ListItem,
+    /// Frecency score of the item.
+    pub score: f64,
+    /// Pinned items are always shown before the others.
+    pub pinned: bool,
+}
+
+/// Search a database.
+///
+/// Finds nothing if the search was cancelled while waiting for the
+/// database.
+fn ranked_items<T: DBItem>(
+    db: &FrecencyDB<T>,
+    input: &str,
+    num_items: usize,
+    cancel: &CancelToken,
+) -> Result<Vec<ListItem>>
+where
+    ListItem: From<Container<T>>,
+{
+    if cancel.is_cancelled() {
+        return Ok(Vec::new());
+    }
+    Ok(db
+        .get_ranked_list(input, Some(num_items))?
+        .into_iter()
+        .map(ListItem::from)
+        .collect())
+}
+
+/// Search a database, scoring the items so they can be blended with the
+/// items of other plugins.
+fn scored_items<T: DBItem>(
+    db: &FrecencyDB<T>,
+    input: &str,
+    num_items: usize,
+    cancel: &CancelToken,
+) -> Result<Vec<ScoredItem>>
+where
+    ListItem: From<Container<T>>,
+{
+    if cancel.is_cancelled() {
+        return Ok(Vec::new());
+    }
+    Ok(db
+        .get_ranked_list(input, Some(num_items))?
+        .into_iter()
+        .map(|cont| ScoredItem {
+            score: db.normalize_score(cont.score),
+            item: ListItem::from(cont),
+        })
+        .collect())
+}
+
+/// Get the pinned items followed by the most used items of a database.
+fn frecent_items<T: DBItem>(
+    db: &FrecencyDB<T>,
+    num_items: usize,
+) -> Result<Vec<FrecentItem>>
+where
+    ListItem: From<Container<T>>,
+{
+    let pinned = db.get_pinned()?.into_iter().map(|cont| (cont, true));
+    let top = db
+        .get_top_ranked(num_items)?
+        .into_iter()
+        .map(|cont| (cont, false));
+    Ok(pinned
+        .chain(top)
+        .map(|(cont, pinned)| FrecentItem {
+            score: cont.score,
+            item: ListItem::from(cont),
+            pinned,
+        })
+        .collect())
 }
 
 pub trait Plugin: Send + Sync {
     // fn init(config: &Config) -> Result<Box<Self>>;
-    fn matcher(&self, config: &Config, input: &str) -> bool;
+    /// The name the plugin is configured by.
+    fn name(&self) -> &str;
+    /// Prefix of the searches for this plugin if the config doesn't set one.
+    fn default_prefix(&self) -> Option<&str> {
+        None
+    }
+    /// Search the plugin's items.
+    ///
+    /// `input` doesn't include the plugin's prefix.  Once `cancel` is
+    /// cancelled the results are thrown away, so slow plugins should check
+    /// it and return early.
     fn search(
         &self,
         config: &Config,
         input: &str,
         num_items: usize,
+        cancel: &CancelToken,
     ) -> Result<Vec<ListItem>>;
-    fn run(&mut self, config: &Config, id: u64) -> Result<()>;
+    /// Search for items to blend with the results of other plugins.
+    ///
+    /// Plugins that don't take part in blended results return nothing.
     #[allow(unused_variables)]
-    fn reload(&mut self, config: &Config) -> Result<Vec<Error>> {
+    fn blended_search(
+        &self,
+        config: &Config,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ScoredItem>> {
+        Ok(Vec::new())
+    }
+    fn run(&self, config: &Config, id: u64) -> Result<()>;
+    /// Items to show when nothing has been typed.
+    #[allow(unused_variables)]
+    fn top_items(
+        &self,
+        config: &Config,
+        num_items: usize,
+    ) -> Result<Vec<FrecentItem>> {
+        Ok(Vec::new())
+    }
+    /// Secondary actions that can be run on an item.
+    #[allow(unused_variables)]
+    fn actions(&self, config: &Config, id: u64) -> Result<Vec<Action>> {
+        Ok(Vec::new())
+    }
+    /// Run one of the actions returned by `actions`.
+    #[allow(unused_variables)]
+    fn run_action(
+        &self,
+        config: &Config,
+        id: u64,
+        action: &str,
+    ) -> Result<ActionResult> {
+        Err(anyhow!("This plugin doesn't support actions"))
+    }
+    /// Pin or unpin an item, returning true if it's now pinned.
+    #[allow(unused_variables)]
+    fn toggle_pin(&self, config: &Config, id: u64) -> Result<bool> {
+        Err(anyhow!("This plugin doesn't support pinning items"))
+    }
+    #[allow(unused_variables)]
+    fn add_alias(&self, config: &Config, alias: &str, id: u64) -> Result<()> {
+        Err(anyhow!("This plugin doesn't support aliases"))
+    }
+    /// Scan for the plugin's items again.
+    ///
+    /// Reloads run on a background thread while the plugin is searched, so
+    /// the new items should be prepared before swapping them in.
+    #[allow(unused_variables)]
+    fn reload(&self, config: &Config) -> Result<Vec<Error>> {
         Ok(Vec::new())
     }
     #[allow(unused_variables)]
     fn register_event_handlers(
-        &mut self,
+        &self,
         config: &Config,
         event_tx: Sender<Event>,
     ) {
@@ -0,0 +1,209 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::matcher::{Matcher, MatcherKind};
+use anyhow::{anyhow, Error, Result};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Options picking which searches a plugin handles.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ModeOptions {
+    /// Searches starting with this only search the plugin.
+    ///
+    /// An empty prefix removes the plugin's built in prefix.
+    pub prefix: Option<String>,
+    /// Whether searches without a prefix search the plugin.
+    pub default: Option<bool>,
+}
+
+/// The options of a plugin in the config file.
+///
+/// The options every plugin takes are followed by the options of type `T`
+/// the plugin declares for itself.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, bound = "T: DeserializeOwned + Default")]
+pub struct PluginOptions<T> {
+    #[serde(flatten)]
+    pub mode: ModeOptions,
+    /// Algorithms used to match searches against the plugin's items.
+    pub matchers: Option<Vec<MatcherKind>>,
+    /// How few items a search has to find for items matching with typos to
+    /// be added.
+    pub typo_fallback: Option<usize>,
+    #[serde(flatten)]
+    pub plugin: T,
+    /// Options neither the launcher nor the plugin know.
+    #[serde(flatten)]
+    unknown: BTreeMap<String, Value>,
+}
+
+impl<T: DeserializeOwned + Default> PluginOptions<T> {
+    /// Read the options of the plugin `name` from the `plugins` section of
+    /// the config file.
+    ///
+    /// Uses the default options if the plugin isn't in the config file.
+    pub fn from_config(plugins: &Value, name: &str) -> Result<Self> {
+        let options = Self::from_config_lenient(plugins, name)?;
+        if let Some(key) = options.unknown.keys().next() {
+            return Err(anyhow!(
+                "Unknown option `{}` for plugin `{}`",
+                key,
+                name
+            ));
+        }
+        Ok(options)
+    }
+
+    /// Read the options of a plugin, allowing options that aren't
+    /// declared.
+    pub(super) fn from_config_lenient(
+        plugins: &Value,
+        name: &str,
+    ) -> Result<Self> {
+        let value = match plugins.get(name) {
+            Some(value) => value,
+            None => return Ok(Self::default()),
+        };
+        let options: Self = serde_json::from_value(value.clone())
+            .map_err(|e| Self::explain_error(value, name, e.into()))?;
+        if matches!(options.matchers.as_deref(), Some([])) {
+            return Err(anyhow!(
+                "The `matchers` option for plugin `{}` is empty",
+                name
+            ));
+        }
+        Ok(options)
+    }
+
+    /// Find which option made reading the options fail.
+    ///
+    /// Serde's errors don't say which field had the wrong type so each
+    /// option is read on its own until one fails.
+    fn explain_error(value: &Value, name: &str, error: Error) -> Error {
+        let options = match value.as_object() {
+            Some(options) => options,
+            None => {
+                return anyhow!(
+                    "The options of plugin `{}` must be an object",
+                    name
+                )
+            }
+        };
+        for (key, option) in options {
+            let mut single = Map::new();
+            single.insert(key.clone(), option.clone());
+            if let Err(e) = serde_json::from_value::<Self>(single.into()) {
+                return anyhow!(
+                    "Invalid `{}` option for plugin `{}`: {}",
+                    key,
+                    name,
+                    e
+                );
+            }
+        }
+        error.context(format!("Invalid options for plugin `{}`", name))
+    }
+
+    /// The matchers the plugin is configured to use.
+    ///
+    /// Uses fuzzy matching if the `matchers` option isn't set.
+    pub fn matchers(&self) -> Vec<Box<dyn Matcher>> {
+        self.matchers_or(&[MatcherKind::Fuzzy])
+    }
+
+    /// The matchers the plugin is configured to use, or `default` if the
+    /// `matchers` option isn't set.
+    pub fn matchers_or(
+        &self,
+        default: &[MatcherKind],
+    ) -> Vec<Box<dyn Matcher>> {
+        self.matchers
+            .as_deref()
+            .unwrap_or(default)
+            .iter()
+            .map(|kind| kind.matcher())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(default)]
+    struct TestOptions {
+        enabled: bool,
+        paths: Vec<String>,
+    }
+
+    fn error(plugins: Value) -> String {
+        PluginOptions::<TestOptions>::from_config(&plugins, "test")
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn read_options() {
+        let plugins = json!({
+            "test": {
+                "prefix": "=",
+                "typo_fallback": 2,
+                "enabled": true,
+                "paths": ["~/a"],
+            }
+        });
+        let options =
+            PluginOptions::<TestOptions>::from_config(&plugins, "test")
+                .unwrap();
+        assert_eq!(options.mode.prefix.as_deref(), Some("="));
+        assert_eq!(options.mode.default, None);
+        assert_eq!(options.typo_fallback, Some(2));
+        assert!(options.plugin.enabled);
+        assert_eq!(options.plugin.paths, vec!["~/a".to_owned()]);
+        assert_eq!(options.matchers().len(), 1);
+
+        let options =
+            PluginOptions::<TestOptions>::from_config(&plugins, "missing")
+                .unwrap();
+        assert!(!options.plugin.enabled);
+    }
+
+    #[test]
+    fn option_errors() {
+        assert_eq!(
+            error(json!({"test": {"enabled": true, "colour": "red"}})),
+            "Unknown option `colour` for plugin `test`"
+        );
+        assert!(error(json!({"test": {"paths": "~/a"}}))
+            .starts_with("Invalid `paths` option for plugin `test`: "));
+        assert!(error(json!({"test": {"matchers": ["fuzy"]}}))
+            .starts_with("Invalid `matchers` option for plugin `test`: "));
+        assert_eq!(
+            error(json!({"test": {"matchers": []}})),
+            "The `matchers` option for plugin `test` is empty"
+        );
+        assert_eq!(
+            error(json!({"test": ["a"]})),
+            "The options of plugin `test` must be an object"
+        );
+    }
+}
@@ -0,0 +1,536 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Plugins run as separate programs.
+//!
+//! The launcher writes requests to the program's stdin and reads responses
+//! and events from its stdout, one JSON object per line.  Requests look
+//! like `{"id": 1, "method": "search", "params": {...}}` and are answered
+//! with `{"id": 1, "result": ...}` or `{"id": 1, "error": "message"}`.
+//!
+//! | method   | params                             | result                 |
+//! |----------|------------------------------------|------------------------|
+//! | `init`   | `{"config": <plugin's config>}`    | `{"prefix": "="}`      |
+//! | `search` | `{"query": "..", "num_items": 10}` | `{"items": [<item>]}`  |
+//! | `run`    | `{"id": <item id>}`                | anything               |
+//! | `reload` | `{}`                               | anything               |
+//!
+//! Items are `{"id": 1, "name": "..", "subtitle": "..", "icon": "..",
+//! "score": 0.5, "matches": [[0, 2]]}` where only `id` and `name` are
+//! required.  `score` is between 0 and 1 and is used to blend the items
+//! with the results of other plugins.  `matches` are the ranges of the
+//! characters of the name to highlight, start inclusive and end exclusive.
+//!
+//! At any time the program can send `{"event": "reload"}` to have the
+//! launcher reload all the plugins, `{"event": "refresh"}` to have it
+//! search again or `{"event": "error", "message": ".."}` to show an error.
+use super::{ModeOptions, Plugin, PluginOptions, ScoredItem};
+use crate::cancel::CancelToken;
+use crate::config::Config;
+use crate::event::Event;
+use crate::ListItem;
+use anyhow::{anyhow, Context as _, Error, Result};
+use log::{debug, error, trace};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::ops::Range;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the program to answer a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often to check whether a search was cancelled while waiting for the
+/// program.
+const CANCEL_POLL: Duration = Duration::from_millis(20);
+
+/// A request sent to the program.
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    id: u64,
+    method: &'a str,
+    params: Value,
+}
+
+/// A line read from the program.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Message {
+    Response {
+        id: u64,
+        #[serde(default)]
+        result: Value,
+        error: Option<String>,
+    },
+    Event(ScriptEvent),
+}
+
+/// An event pushed by the program.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum ScriptEvent {
+    Reload,
+    Refresh,
+    Error { message: String },
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct InitResult {
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    items: Vec<ScriptItem>,
+}
+
+/// An item found by the program.
+#[derive(Debug, Deserialize)]
+struct ScriptItem {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    subtitle: String,
+    #[serde(default)]
+    icon: String,
+    #[serde(default)]
+    score: f64,
+    #[serde(default)]
+    matches: Vec<(usize, usize)>,
+}
+
+/// Turn the ranges sent by the program into ranges of the name's
+/// characters the ui can highlight.
+///
+/// The ranges are clamped to the name, sorted and merged where they
+/// overlap, and empty ones are dropped.
+fn match_ranges(name: &str, matches: Vec<(usize, usize)>) -> Vec<Range<usize>> {
+    let len = name.chars().count();
+    let mut matches: Vec<_> = matches
+        .into_iter()
+        .map(|(start, end)| start.min(len)..end.min(len))
+        .filter(|range| !range.is_empty())
+        .collect();
+    matches.sort_by_key(|range| range.start);
+    let mut ranges: Vec<Range<usize>> = Vec::with_capacity(matches.len());
+    for range in matches {
+        match ranges.last_mut() {
+            Some(last) if last.end >= range.start => {
+                last.end = last.end.max(range.end)
+            }
+            _ => ranges.push(range),
+        }
+    }
+    ranges
+}
+
+impl From<ScriptItem> for ListItem {
+    fn from(item: ScriptItem) -> Self {
+        ListItem {
+            matches: match_ranges(&item.name, item.matches),
+            name: item.name,
+            icon: item.icon,
+            id: item.id,
+            subtitle: item.subtitle,
+            source: String::new(),
+            badges: Vec::new(),
+        }
+    }
+}
+
+/// A running plugin program.
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    responses: Receiver<(u64, Result<Value>)>,
+    next_id: u64,
+}
+
+impl Process {
+    /// Start the program, forwarding its events to `events` once it's set.
+    fn spawn(
+        name: &str,
+        command: &[String],
+        events: Arc<Mutex<Option<Sender<Event>>>>,
+    ) -> Result<Self> {
+        let (program, args) = command.split_first().ok_or_else(|| {
+            anyhow!("The `command` option for plugin `{}` is empty", name)
+        })?;
+        let program = shellexpand::full(program)
+            .with_context(|| format!("Error expanding path {}", program))?;
+        let mut child = Command::new(program.as_ref())
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| {
+                format!("Error starting plugin `{}` ({:?})", name, command)
+            })?;
+        let stdin = child.stdin.take().expect("Stdin is piped");
+        let stdout = child.stdout.take().expect("Stdout is piped");
+        let (tx, responses) = mpsc::channel();
+        let name = name.to_owned();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        error!("Error reading from plugin `{}`: {}", name, e);
+                        return;
+                    }
+                };
+                trace!("Plugin `{}` sent {}", name, line);
+                match serde_json::from_str(&line) {
+                    Ok(Message::Response { id, result, error }) => {
+                        let result = match error {
+                            Some(msg) => {
+                                Err(anyhow!("Plugin `{}`: {}", name, msg))
+                            }
+                            None => Ok(result),
+                        };
+                        if tx.send((id, result)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Message::Event(event)) => {
+                        let event = match event {
+                            ScriptEvent::Reload => Event::Reload,
+                            ScriptEvent::Refresh => Event::Refresh,
+                            ScriptEvent::Error { message } => Event::Error(
+                                format!("Plugin `{}`: {}", name, message),
+                            ),
+                        };
+                        match &*events.lock().unwrap() {
+                            Some(events) => {
+                                if let Err(e) = events.send(event) {
+                                    error!(
+                                        "Error sending event to ui: {:?}",
+                                        e
+                                    );
+                                }
+                            }
+                            None => debug!(
+                                "Dropping event {:?} of plugin `{}`",
+                                event, name
+                            ),
+                        }
+                    }
+                    Err(e) => error!(
+                        "Invalid message from plugin `{}`: {}: {}",
+                        name, e, line
+                    ),
+                }
+            }
+            debug!("Plugin `{}` closed its stdout", name);
+        });
+        Ok(Process {
+            child,
+            stdin,
+            responses,
+            next_id: 0,
+        })
+    }
+
+    /// Send a request and wait for its response.
+    fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.request_until(method, params, &CancelToken::new())
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Send a request, giving up on the response if `cancel` is cancelled.
+    ///
+    /// Returns `None` if the request was cancelled.  Its response is
+    /// skipped when it arrives.
+    fn request_until(
+        &mut self,
+        method: &str,
+        params: Value,
+        cancel: &CancelToken,
+    ) -> Result<Option<Value>> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let mut line = serde_json::to_string(&Request { id, method, params })?;
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .context("Error writing to plugin")?;
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        loop {
+            if cancel.is_cancelled() {
+                return Ok(None);
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.responses.recv_timeout(timeout.min(CANCEL_POLL)) {
+                Ok((resp_id, result)) if resp_id == id => {
+                    return result.map(Some)
+                }
+                // The response to a request that timed out or was cancelled
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) if timeout > CANCEL_POLL => {
+                    continue
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(anyhow!("Plugin didn't answer `{}`", method))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow!("Plugin exited"))
+                }
+            }
+        }
+    }
+
+    fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Options of a script plugin.
+///
+/// All the plugin's options are also sent to the program.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ScriptOptions {
+    /// The program to run and its arguments.
+    command: Vec<String>,
+}
+
+/// A plugin run as a separate program.
+pub struct Script {
+    name: String,
+    command: Vec<String>,
+    /// The plugin's options, sent to the program when it starts.
+    options: Value,
+    prefix: Option<String>,
+    process: Mutex<Process>,
+    events: Arc<Mutex<Option<Sender<Event>>>>,
+}
+
+impl Script {
+    /// Start the program of the script plugin `name` if it's configured.
+    ///
+    /// Returns `None` if the plugin's `command` option isn't set.
+    pub fn init(
+        config: &Config,
+        name: &str,
+    ) -> Option<Result<(Self, ModeOptions)>> {
+        let plugins = &config.file_options.plugins;
+        let value = plugins.get(name)?;
+        value.get("command")?;
+        Some(
+            PluginOptions::<ScriptOptions>::from_config_lenient(plugins, name)
+                .and_then(|options| {
+                    let script = Script::start(
+                        name,
+                        options.plugin.command,
+                        value.clone(),
+                    )?;
+                    Ok((script, options.mode))
+                }),
+        )
+    }
+
+    fn start(name: &str, command: Vec<String>, options: Value) -> Result<Self> {
+        let events = Arc::new(Mutex::new(None));
+        let mut process = Process::spawn(name, &command, events.clone())?;
+        let init: InitResult = serde_json::from_value(
+            process.request("init", json!({ "config": options }))?,
+        )
+        .unwrap_or_default();
+        Ok(Script {
+            name: name.to_owned(),
+            command,
+            options,
+            prefix: init.prefix,
+            process: Mutex::new(process),
+            events,
+        })
+    }
+
+    fn search_items(
+        &self,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ScriptItem>> {
+        let result = match self.process.lock().unwrap().request_until(
+            "search",
+            json!({ "query": input, "num_items": num_items }),
+            cancel,
+        )? {
+            Some(result) => result,
+            None => return Ok(Vec::new()),
+        };
+        let mut items = serde_json::from_value::<SearchResult>(result)
+            .with_context(|| {
+                format!("Invalid search result from plugin `{}`", self.name)
+            })?
+            .items;
+        items.truncate(num_items);
+        Ok(items)
+    }
+}
+
+impl Plugin for Script {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn default_prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    fn search(
+        &self,
+        _: &Config,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ListItem>> {
+        Ok(self
+            .search_items(input, num_items, cancel)?
+            .into_iter()
+            .map(ListItem::from)
+            .collect())
+    }
+
+    fn blended_search(
+        &self,
+        _: &Config,
+        input: &str,
+        num_items: usize,
+        cancel: &CancelToken,
+    ) -> Result<Vec<ScoredItem>> {
+        Ok(self
+            .search_items(input, num_items, cancel)?
+            .into_iter()
+            .map(|item| ScoredItem {
+                score: item.score.clamp(0.0, 1.0),
+                item: ListItem::from(item),
+            })
+            .collect())
+    }
+
+    fn run(&self, _: &Config, id: u64) -> Result<()> {
+        self.process
+            .lock()
+            .unwrap()
+            .request("run", json!({ "id": id }))?;
+        Ok(())
+    }
+
+    /// Reload the plugin, restarting the program if it exited.
+    fn reload(&self, _: &Config) -> Result<Vec<Error>> {
+        let mut process = self.process.lock().unwrap();
+        if !process.is_running() {
+            debug!("Restarting plugin `{}`", self.name);
+            *process =
+                Process::spawn(&self.name, &self.command, self.events.clone())?;
+            process.request("init", json!({ "config": self.options }))?;
+            return Ok(Vec::new());
+        }
+        process.request("reload", json!({}))?;
+        Ok(Vec::new())
+    }
+
+    fn register_event_handlers(
+        &self,
+        _config: &Config,
+        event_tx: Sender<Event>,
+    ) {
+        *self.events.lock().unwrap() = Some(event_tx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plugin answering every request with the same result.
+    fn echo_plugin(result: &str) -> Result<Script> {
+        let script = format!(
+            r#"while read -r line; do
+                id=$(echo "$line" | sed 's/^{{"id":\([0-9]*\).*/\1/')
+                echo '{{"event":"refresh"}}'
+                echo "{{\"id\":$id,\"result\":{}}}"
+            done"#,
+            result.replace('"', "\\\"")
+        );
+        Script::start(
+            "echo",
+            vec!["sh".to_owned(), "-c".to_owned(), script],
+            json!({}),
+        )
+    }
+
+    #[test]
+    fn protocol() {
+        let plugin = echo_plugin(concat!(
+            r#"{"prefix":"=","#,
+            r#""items":[{"id":4,"name":"Four","matches":[[0,2]]}]}"#
+        ))
+        .unwrap();
+        assert_eq!(plugin.default_prefix(), Some("="));
+        let (tx, rx) = mpsc::channel();
+        *plugin.events.lock().unwrap() = Some(tx);
+        let cancel = CancelToken::new();
+        let items = plugin.search_items("2+2", 10, &cancel).unwrap();
+        assert_eq!(items.len(), 1);
+        let item = ListItem::from(items.into_iter().next().unwrap());
+        assert_eq!((item.id, item.name.as_str()), (4, "Four"));
+        assert_eq!(item.matches, vec![0..2]);
+        match rx.recv_timeout(REQUEST_TIMEOUT) {
+            Ok(Event::Refresh) => {}
+            other => panic!("Expected a refresh event, got {:?}", other),
+        }
+        cancel.cancel();
+        assert!(plugin.search_items("2+2", 10, &cancel).unwrap().is_empty());
+    }
+
+    #[test]
+    fn match_ranges_are_cleaned() {
+        assert_eq!(
+            match_ranges("Fünf", vec![(3, 9), (2, 2), (0, 1), (1, 3), (5, 7)]),
+            vec![0..4]
+        );
+        assert_eq!(
+            match_ranges("Four", vec![(2, 3), (0, 1)]),
+            vec![0..1, 2..3]
+        );
+        assert!(match_ranges("Four", vec![(3, 1)]).is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let plugin = echo_plugin(r#"{"items":"none"}"#).unwrap();
+        assert_eq!(plugin.default_prefix(), None);
+        let cancel = CancelToken::new();
+        assert!(plugin.search_items("x", 10, &cancel).is_err());
+        assert!(Script::start("missing", vec![], json!({})).is_err());
+        let exits = Script::start("exits", vec!["true".to_owned()], json!({}));
+        assert!(exits.is_err());
+    }
+}
@@ -0,0 +1,270 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::matcher::{self, Match, Matcher};
+use crate::normalize::SearchText;
+
+/// Characters starting the terms of the extended syntax.
+const SYNTAX_PREFIXES: [char; 3] = ['\'', '^', '!'];
+
+/// How a term of a query is matched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TermKind {
+    /// Matched with the configured matchers.
+    Fuzzy,
+    /// `'term`: the text contains the term.
+    Exact,
+    /// `^term`: the text starts with the term.
+    Prefix,
+    /// `term$`: the text ends with the term.
+    Suffix,
+    /// `^term$`: the text is the term.
+    Equal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    kind: TermKind,
+    /// `!term`: the text must not match the term.
+    negated: bool,
+    text: String,
+}
+
+impl Term {
+    /// Parse a term, returning `None` if it has no text to match.
+    fn parse(token: &str) -> Option<Term> {
+        let (negated, token) = match token.strip_prefix('!') {
+            Some(token) => (true, token),
+            None => (false, token),
+        };
+        let (kind, text) = if let Some(text) = token.strip_prefix('\'') {
+            (TermKind::Exact, text)
+        } else if let Some(text) = token.strip_prefix('^') {
+            match text.strip_suffix('$') {
+                Some(text) => (TermKind::Equal, text),
+                None => (TermKind::Prefix, text),
+            }
+        } else if let Some(text) = token.strip_suffix('$') {
+            (TermKind::Suffix, text)
+        } else if negated {
+            // Like fzf, excluded terms are matched exactly
+            (TermKind::Exact, token)
+        } else {
+            (TermKind::Fuzzy, token)
+        };
+        if text.is_empty() {
+            return None;
+        }
+        Some(Term {
+            kind,
+            negated,
+            text: text.to_owned(),
+        })
+    }
+
+    /// Match the term against a text.
+    ///
+    /// The indices of the match are into the original text.
+    fn matches(
+        &self,
+        matchers: &[Box<dyn Matcher>],
+        text: &SearchText,
+    ) -> Option<Match> {
+        let folded = &text.folded;
+        let start = match self.kind {
+            TermKind::Fuzzy => {
+                return matcher::best_match(matchers, text, &self.text)
+            }
+            TermKind::Exact => folded.find(&self.text)?,
+            TermKind::Prefix if folded.starts_with(&self.text) => 0,
+            TermKind::Suffix if folded.ends_with(&self.text) => {
+                folded.len() - self.text.len()
+            }
+            TermKind::Equal if *folded == self.text => 0,
+            _ => return None,
+        };
+        let start = folded[..start].chars().count();
+        let indices: Vec<usize> =
+            (start..start + self.text.chars().count()).collect();
+        Some(Match {
+            score: 1.0,
+            indices: text.original_indices(&indices),
+        })
+    }
+}
+
+/// A search using the extended search syntax.
+///
+/// Like fzf, the search is split at whitespace into terms which must all
+/// match.  `'term` matches text containing `term`, `^term` text starting
+/// with it and `term$` text ending with it.  `!term` matches text that
+/// doesn't contain `term`.  Other terms are matched with the plugin's
+/// matchers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+impl Query {
+    /// Parse a folded search string.
+    ///
+    /// Returns `None` if none of the terms use the extended syntax, in
+    /// which case the whole search should be matched as is.
+    pub fn parse(search: &str) -> Option<Query> {
+        if !Query::is_extended(search) {
+            return None;
+        }
+        Some(Query {
+            terms: search.split_whitespace().filter_map(Term::parse).collect(),
+        })
+    }
+
+    /// Whether a search uses the extended syntax.
+    pub fn is_extended(search: &str) -> bool {
+        search.split_whitespace().any(|token| {
+            token.starts_with(&SYNTAX_PREFIXES[..])
+                || (token.len() > 1 && token.ends_with('$'))
+        })
+    }
+
+    /// Remove the extended syntax from a search so it's matched as typed,
+    /// as when a search was changed by being retyped with another keyboard
+    /// layout.
+    pub fn strip_syntax(search: &str) -> String {
+        search
+            .split_whitespace()
+            .map(|token| {
+                let token = token.trim_start_matches(&SYNTAX_PREFIXES[..]);
+                match token.strip_suffix('$') {
+                    Some(rest) if !rest.is_empty() => rest,
+                    _ => token,
+                }
+            })
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Match a text against all the terms of the query.
+    ///
+    /// The score is the average score of the terms and the indices are
+    /// into the original text.
+    pub fn matches(
+        &self,
+        matchers: &[Box<dyn Matcher>],
+        text: &SearchText,
+    ) -> Option<Match> {
+        let mut score = 0.0;
+        let mut matched = 0;
+        let mut indices = Vec::new();
+        for term in &self.terms {
+            match (term.matches(matchers, text), term.negated) {
+                (Some(_), true) | (None, false) => return None,
+                (Some(m), false) => {
+                    score += m.score;
+                    matched += 1;
+                    indices.extend(m.indices);
+                }
+                (None, true) => {}
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        Some(Match {
+            score: if matched > 0 {
+                score / matched as f64
+            } else {
+                0.0
+            },
+            indices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalize::fold;
+
+    fn matches(search: &str, text: &str) -> Option<Vec<usize>> {
+        Query::parse(&fold(search))
+            .unwrap()
+            .matches(&matcher::default_matchers(), &SearchText::new(text))
+            .map(|m| m.indices)
+    }
+
+    #[test]
+    fn plain_queries() {
+        assert_eq!(Query::parse("firefox"), None);
+        assert_eq!(Query::parse("lo w"), None);
+        assert_eq!(Query::parse("$ c++"), None);
+    }
+
+    #[test]
+    fn strip_syntax() {
+        assert!(Query::is_extended("don 't"));
+        assert!(!Query::is_extended("don't $"));
+        assert_eq!(
+            Query::strip_syntax("'ex ^eq$ !not $ fuz"),
+            "ex eq not $ fuz"
+        );
+        assert_eq!(Query::parse(&Query::strip_syntax("^fire !dev")), None);
+    }
+
+    #[test]
+    fn parse_terms() {
+        let query = Query::parse("'ex ^pre suf$ ^eq$ !not fuz ^").unwrap();
+        let kinds: Vec<(TermKind, bool, &str)> = query
+            .terms
+            .iter()
+            .map(|term| (term.kind, term.negated, term.text.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (TermKind::Exact, false, "ex"),
+                (TermKind::Prefix, false, "pre"),
+                (TermKind::Suffix, false, "suf"),
+                (TermKind::Equal, false, "eq"),
+                (TermKind::Exact, true, "not"),
+                (TermKind::Fuzzy, false, "fuz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn match_terms() {
+        assert_eq!(matches("'fox", "Firefox"), Some(vec![4, 5, 6]));
+        assert_eq!(matches("'fx", "Firefox"), None);
+        assert_eq!(matches("^fire", "Firefox"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(matches("^fox", "Firefox"), None);
+        assert_eq!(matches("fox$", "Firefox"), Some(vec![4, 5, 6]));
+        assert_eq!(matches("^firefox$", "Firefox"), Some((0..7).collect()));
+        assert_eq!(matches("^fire$", "Firefox"), None);
+        assert_eq!(matches("^café$", "Cafe"), Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn and_and_exclude() {
+        assert_eq!(matches("^fire !dev", "Firefox"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(matches("^fire !dev", "Firefox Developer Edition"), None);
+        assert_eq!(
+            matches("fx 'dev", "Firefox Developer"),
+            Some(vec![0, 6, 8, 9, 10])
+        );
+        assert_eq!(matches("!dev", "Gimp"), Some(vec![]));
+    }
+}
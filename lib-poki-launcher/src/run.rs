@@ -17,9 +17,10 @@
 use anyhow::{Context as _, Error, Result};
 use nix::unistd::{getpid, setpgid};
 use std::os::unix::process::CommandExt as _;
+use std::path::Path;
 use std::process::{Child, Command, Stdio};
 
-pub fn parse_command_string<'a>(exec: &'a str) -> (String, Vec<&'a str>) {
+pub fn parse_command_string(exec: &str) -> (String, Vec<&str>) {
     let mut iter = exec.split(' ');
     let cmd = iter.next().expect("Empty Exec").to_owned();
     let args = iter.collect();
@@ -44,7 +45,16 @@ pub fn run_bg(mut command: Command) -> Result<Child> {
             Ok(())
         });
     }
-    Ok(command
+    command
         .spawn()
-        .with_context(|| format!("Execution of command`{:?}`", command))?)
+        .with_context(|| format!("Execution of command`{:?}`", command))
+}
+
+/// Open a file or directory with its default app.
+pub fn xdg_open(path: &Path) -> Result<()> {
+    let mut command = Command::new("xdg-open");
+    command.arg(path.as_os_str());
+    let _ = run_bg(command)
+        .with_context(|| format!("Error opening {}", path.display()))?;
+    Ok(())
 }
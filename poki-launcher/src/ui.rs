@@ -14,19 +14,27 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
-use anyhow::Error;
+use anyhow::{Error, Result};
 use cstr::*;
 use lazy_static::lazy_static;
-use lib_poki_launcher::{event::Event, ListItem, PokiLauncher as Launcher};
+use lib_poki_launcher::{
+    cancel::CancelToken, event::Event, Action, ActionResult, ListItem,
+    PokiLauncher as Launcher,
+};
 use log::{debug, error, trace, warn};
 use poki_launcher_notifier::{self as notifier, Notifier};
 use qmetaobject::*;
 use std::cell::{Cell, RefCell};
 use std::convert::From;
+use std::ops::Range;
+use std::sync::mpsc::{self, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
 const MAX_APPS_SHOWN: usize = 5;
+/// How long to wait for more typing before searching.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(30);
 
 lazy_static! {
     pub static ref LAUNCHER: Arc<Mutex<Option<Launcher>>> =
@@ -41,6 +49,16 @@ thread_local! {
 struct PokiLauncher {
     base: qt_base_class!(trait QObject),
     list: Vec<ListItem>,
+    query: String,
+    highlight_color: String,
+    /// Actions of the selected item while the action list is open.
+    actions: Vec<Action>,
+    /// Number of the latest search, older searches' results are dropped.
+    generation: u64,
+    /// Cancels the latest search.
+    cancel: CancelToken,
+    /// Sends searches to the search thread.
+    searches: Option<Sender<SearchRequest>>,
     model: qt_property!(RefCell<SimpleListModel<QListItem>>; NOTIFY model_changed),
     selected: qt_property!(u64; NOTIFY selected_changed),
     visible: qt_property!(bool; NOTIFY visible_changed),
@@ -49,6 +67,11 @@ struct PokiLauncher {
     has_error: qt_property!(bool; NOTIFY has_error_changed),
     error_msg: qt_property!(QString; NOTIFY error_msg_changed),
     error_msg_full: qt_property!(QString; NOTIFY error_msg_changed),
+    correction: qt_property!(QString; NOTIFY correction_changed),
+    mode: qt_property!(QString; NOTIFY mode_changed),
+    actions_model: qt_property!(RefCell<SimpleListModel<QActionItem>>; NOTIFY actions_changed),
+    actions_visible: qt_property!(bool; NOTIFY actions_changed),
+    selected_action: qt_property!(i32; NOTIFY actions_changed),
 
     window_height: qt_property!(i32; NOTIFY settings_changed),
     window_width: qt_property!(i32; NOTIFY settings_changed),
@@ -64,6 +87,8 @@ struct PokiLauncher {
     input_font_size: qt_property!(i32; NOTIFY settings_changed),
     app_font_size: qt_property!(i32; NOTIFY settings_changed),
     input_box_ratio: qt_property!(f32; NOTIFY settings_changed),
+    /// Show which plugin each item came from.
+    show_sources: qt_property!(bool; NOTIFY settings_changed),
 
     init: qt_method!(fn(&mut self)),
     search: qt_method!(fn(&mut self, text: String)),
@@ -71,6 +96,15 @@ struct PokiLauncher {
     down: qt_method!(fn(&mut self)),
     up: qt_method!(fn(&mut self)),
     run: qt_method!(fn(&mut self)),
+    add_alias: qt_method!(fn(&mut self, alias: String)),
+    toggle_pin: qt_method!(fn(&mut self)),
+    next_mode: qt_method!(fn(&mut self)),
+    prev_mode: qt_method!(fn(&mut self)),
+    show_actions: qt_method!(fn(&mut self)),
+    hide_actions: qt_method!(fn(&mut self)),
+    action_down: qt_method!(fn(&mut self)),
+    action_up: qt_method!(fn(&mut self)),
+    run_action: qt_method!(fn(&mut self) -> bool),
     hide: qt_method!(fn(&mut self)),
     exit: qt_method!(fn(&mut self)),
 
@@ -81,6 +115,10 @@ struct PokiLauncher {
     settings_changed: qt_signal!(),
     has_error_changed: qt_signal!(),
     error_msg_changed: qt_signal!(),
+    correction_changed: qt_signal!(),
+    mode_changed: qt_signal!(),
+    actions_changed: qt_signal!(),
+    copy_requested: qt_signal!(text: QString),
 }
 
 impl PokiLauncher {
@@ -123,10 +161,13 @@ impl PokiLauncher {
             launcher.config.file_options.app_separator_color.clone(),
         )
         .into();
+        self.highlight_color =
+            prepend_hash(launcher.config.file_options.highlight_color.clone());
 
         self.input_font_size = launcher.config.file_options.input_font_size;
         self.app_font_size = launcher.config.file_options.app_font_size;
         self.input_box_ratio = launcher.config.file_options.input_box_ratio;
+        self.show_sources = launcher.config.file_options.blend_results;
 
         self.settings_changed();
 
@@ -145,6 +186,8 @@ impl PokiLauncher {
             qptr.as_pinned().map(|self_| {
                 self_.borrow_mut().visible = true;
                 self_.borrow().visible_changed();
+                let query = self_.borrow().query.clone();
+                self_.borrow_mut().search(query);
             });
         });
         thread::spawn(move || loop {
@@ -170,10 +213,17 @@ impl PokiLauncher {
 
         // Setup desktop file change notifier and callback
         let qptr = QPointer::from(&*self);
-        let callback = qmetaobject::queued_callback(move |err| {
-            qptr.as_pinned().map(|self_| match err {
-                Some(err) => self_.borrow_mut().show_error(err),
-                None => self_.borrow_mut().load(),
+        let callback = qmetaobject::queued_callback(move |event| {
+            qptr.as_pinned().map(|self_| match event {
+                Ok(Event::Reload) => self_.borrow_mut().load(),
+                Ok(Event::Refresh) => {
+                    let query = self_.borrow().query.clone();
+                    self_.borrow_mut().search(query);
+                }
+                Ok(Event::Error(msg)) => {
+                    self_.borrow_mut().show_error(Error::msg(msg))
+                }
+                Err(err) => self_.borrow_mut().show_error(err),
             });
         });
         let event_rx = launcher.register_event_handlers();
@@ -181,18 +231,66 @@ impl PokiLauncher {
             match event_rx.recv() {
                 Ok(event) => {
                     debug!("Received event {:?}", event);
-                    match event {
-                        Event::Reload => callback(None),
-                    }
+                    callback(Ok(event));
                 }
-                Err(e) => callback(Some(Error::new(e))),
+                Err(e) => callback(Err(Error::new(e))),
             }
         });
 
         *LAUNCHER.lock().expect("Mutex poisoned") = Some(launcher);
+        self.searches = Some(self.start_search_thread());
         self.load();
     }
 
+    /// Start the thread searches run on so typing doesn't wait for them.
+    fn start_search_thread(&self) -> Sender<SearchRequest> {
+        let (tx, rx) = mpsc::channel::<SearchRequest>();
+        let qptr = QPointer::from(&*self);
+        let callback = qmetaobject::queued_callback(move |results| {
+            qptr.as_pinned().map(|self_| {
+                self_.borrow_mut().show_results(results);
+            });
+        });
+        thread::spawn(move || {
+            while let Ok(mut request) = rx.recv() {
+                // Only search for the last of several quick keystrokes
+                while let Ok(newer) = rx.recv_timeout(SEARCH_DEBOUNCE) {
+                    request = newer;
+                }
+                if request.cancel.is_cancelled() {
+                    continue;
+                }
+                let mut launcher =
+                    LAUNCHER.lock().expect("Launcher Mutex Poisoned");
+                let launcher = match launcher.as_mut() {
+                    Some(launcher) => launcher,
+                    None => continue,
+                };
+                let list = launcher.search(
+                    &request.query,
+                    MAX_APPS_SHOWN,
+                    &request.cancel,
+                );
+                let correction = match launcher.correction() {
+                    Some(correction) => format!(
+                        "{} ({} layout)",
+                        correction.query, correction.layout
+                    ),
+                    None => String::new(),
+                };
+                if request.cancel.is_cancelled() {
+                    continue;
+                }
+                callback(SearchResults {
+                    generation: request.generation,
+                    list,
+                    correction,
+                });
+            }
+        });
+        tx
+    }
+
     fn set_selected(&mut self, selected: u64) {
         self.selected = selected;
         self.selected_changed();
@@ -202,20 +300,42 @@ impl PokiLauncher {
         self.selected
     }
 
+    /// Start searching, cancelling the previous search.
+    ///
+    /// The results are shown by `show_results` once they're found.
     fn search(&mut self, text: String) {
-        self.list = match LAUNCHER
-            .lock()
-            .expect("Launcher Mutex Poisoned")
-            .as_mut()
-            .unwrap()
-            .search(&text, MAX_APPS_SHOWN)
-        {
+        self.query = text.clone();
+        self.hide_actions();
+        self.cancel.cancel();
+        self.cancel = CancelToken::new();
+        self.generation += 1;
+        let request = SearchRequest {
+            generation: self.generation,
+            query: text,
+            cancel: self.cancel.clone(),
+        };
+        if let Some(searches) = &self.searches {
+            if searches.send(request).is_err() {
+                error!("The search thread stopped");
+            }
+        }
+    }
+
+    /// Show the results of a search unless a newer search was started.
+    fn show_results(&mut self, results: SearchResults) {
+        if results.generation != self.generation {
+            trace!("Dropping results of search {}", results.generation);
+            return;
+        }
+        self.list = match results.list {
             Ok(list) => list,
             Err(e) => {
                 error!("{:?}", e);
                 return;
             }
         };
+        self.correction = results.correction.into();
+        self.correction_changed();
         if !self.has_moved
             || !self.list.iter().any(|item| item.id == self.get_selected())
         {
@@ -225,13 +345,26 @@ impl PokiLauncher {
                 self.set_selected(0);
             }
         }
+        let highlight_color = &self.highlight_color;
         self.model.borrow_mut().reset_data(
-            self.list.clone().into_iter().map(QListItem::from).collect(),
+            self.list
+                .iter()
+                .map(|item| QListItem::new(item, highlight_color))
+                .collect(),
         );
     }
 
+    /// Reload the plugins in the background.
+    ///
+    /// Searches keep showing the previous items until the reload is done,
+    /// `loading` only tells the user a reload is running.
     fn load(&mut self) {
         trace!("Loading...");
+        let reloader =
+            match LAUNCHER.lock().expect("Launcher Mutex Poisoned").as_ref() {
+                Some(launcher) => launcher.reloader(),
+                None => return,
+            };
         self.loading = true;
         self.loading_changed();
         let qptr = QPointer::from(&*self);
@@ -241,17 +374,13 @@ impl PokiLauncher {
                 None => {
                     self_.borrow_mut().loading = false;
                     self_.borrow().loading_changed();
+                    let query = self_.borrow().query.clone();
+                    self_.borrow_mut().search(query);
                 }
             });
         });
         thread::spawn(move || {
-            match LAUNCHER
-                .lock()
-                .expect("Launcher Mutex Poisoned")
-                .as_mut()
-                .unwrap()
-                .reload()
-            {
+            match reloader.reload() {
                 Ok(_) => callback(None),
                 Err(e) => callback(Some(e)),
             }
@@ -297,24 +426,28 @@ impl PokiLauncher {
         }
     }
 
+    /// The selected item of the list the user sees.
+    fn selected_item(&self) -> Option<ListItem> {
+        self.list
+            .iter()
+            .find(|item| item.id == self.get_selected())
+            .cloned()
+    }
+
     fn run(&mut self) {
         trace!("Run");
-        if self.list.is_empty() {
-            return;
-        }
+        let item = match self.selected_item() {
+            Some(item) => item,
+            None => return,
+        };
         self.has_moved = false;
-        let item = self
-            .list
-            .iter()
-            .find(|item| item.id == self.get_selected())
-            .unwrap();
 
         if let Err(e) = LAUNCHER
             .lock()
             .expect("Launcher Mutex Poisoned")
             .as_mut()
             .unwrap()
-            .run(item.id)
+            .run(&item.source, item.id)
         {
             self.show_error(e);
         }
@@ -324,6 +457,162 @@ impl PokiLauncher {
         self.set_selected(0);
     }
 
+    fn add_alias(&mut self, alias: String) {
+        trace!("Add alias");
+        let alias = alias.trim();
+        let item = match self.selected_item() {
+            Some(item) if !alias.is_empty() => item,
+            _ => return,
+        };
+        if let Err(e) = LAUNCHER
+            .lock()
+            .expect("Launcher Mutex Poisoned")
+            .as_mut()
+            .unwrap()
+            .add_alias(alias, &item.source, item.id)
+        {
+            self.show_error(e);
+        }
+    }
+
+    fn toggle_pin(&mut self) {
+        trace!("Toggle pin");
+        let item = match self.selected_item() {
+            Some(item) => item,
+            None => return,
+        };
+        let res = LAUNCHER
+            .lock()
+            .expect("Launcher Mutex Poisoned")
+            .as_mut()
+            .unwrap()
+            .toggle_pin(&item.source, item.id);
+        match res {
+            Ok(pinned) => {
+                debug!("Pinned: {}", pinned);
+                self.search(self.query.clone());
+            }
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    fn next_mode(&mut self) {
+        trace!("Next mode");
+        self.switch_mode(Launcher::next_mode);
+    }
+
+    fn prev_mode(&mut self) {
+        trace!("Previous mode");
+        self.switch_mode(Launcher::prev_mode);
+    }
+
+    /// Change which plugin is searched and search again.
+    fn switch_mode(&mut self, switch: fn(&mut Launcher)) {
+        let mode = {
+            let mut launcher =
+                LAUNCHER.lock().expect("Launcher Mutex Poisoned");
+            let launcher = launcher.as_mut().unwrap();
+            switch(launcher);
+            launcher.mode().unwrap_or_default().to_owned()
+        };
+        self.mode = mode.into();
+        self.mode_changed();
+        self.search(self.query.clone());
+    }
+
+    fn show_actions(&mut self) {
+        trace!("Show actions");
+        let item = match self.selected_item() {
+            Some(item) => item,
+            None => return,
+        };
+        let actions = LAUNCHER
+            .lock()
+            .expect("Launcher Mutex Poisoned")
+            .as_ref()
+            .unwrap()
+            .actions(&item.source, item.id);
+        self.actions = match actions {
+            Ok(actions) => actions,
+            Err(e) => {
+                self.show_error(e);
+                return;
+            }
+        };
+        if self.actions.is_empty() {
+            return;
+        }
+        self.actions_model.borrow_mut().reset_data(
+            self.actions
+                .iter()
+                .map(|action| QActionItem {
+                    name: action.name.clone(),
+                })
+                .collect(),
+        );
+        self.selected_action = 0;
+        self.actions_visible = true;
+        self.actions_changed();
+    }
+
+    fn hide_actions(&mut self) {
+        if !self.actions_visible {
+            return;
+        }
+        self.actions.clear();
+        self.actions_visible = false;
+        self.actions_changed();
+    }
+
+    fn action_down(&mut self) {
+        if self.selected_action + 1 < self.actions.len() as i32 {
+            self.selected_action += 1;
+            self.actions_changed();
+        }
+    }
+
+    fn action_up(&mut self) {
+        if self.selected_action > 0 {
+            self.selected_action -= 1;
+            self.actions_changed();
+        }
+    }
+
+    /// Run the selected action, returning true if the launcher should hide.
+    fn run_action(&mut self) -> bool {
+        trace!("Run action");
+        let action = match self.actions.get(self.selected_action as usize) {
+            Some(action) => action.id.clone(),
+            None => return false,
+        };
+        self.hide_actions();
+        let item = match self.selected_item() {
+            Some(item) => item,
+            None => return false,
+        };
+        let res = LAUNCHER
+            .lock()
+            .expect("Launcher Mutex Poisoned")
+            .as_mut()
+            .unwrap()
+            .run_action(&item.source, item.id, &action);
+        match res {
+            Ok(ActionResult::Hide) => true,
+            Ok(ActionResult::Copy(text)) => {
+                self.copy_requested(text.into());
+                true
+            }
+            Ok(ActionResult::Refresh) => {
+                self.search(self.query.clone());
+                false
+            }
+            Err(e) => {
+                self.show_error(e);
+                false
+            }
+        }
+    }
+
     fn hide(&mut self) {
         trace!("Hide");
         self.has_moved = false;
@@ -354,23 +643,76 @@ impl PokiLauncher {
     }
 }
 
+/// A search sent to the search thread.
+struct SearchRequest {
+    generation: u64,
+    query: String,
+    cancel: CancelToken,
+}
+
+/// The results of a search, sent back from the search thread.
+struct SearchResults {
+    generation: u64,
+    list: Result<Vec<ListItem>>,
+    correction: String,
+}
+
 #[derive(Default, Clone, SimpleListItem)]
 struct QListItem {
     pub name: String,
+    /// The name as rich text with the matched characters highlighted.
+    pub markup: String,
     pub id: u64,
     pub icon: String,
+    pub subtitle: String,
+    /// Name of the plugin the item came from.
+    pub source: String,
+    /// Labels of the item's badges, separated by commas.
+    pub badges: String,
 }
 
-impl From<ListItem> for QListItem {
-    fn from(item: ListItem) -> QListItem {
+impl QListItem {
+    fn new(item: &ListItem, highlight_color: &str) -> QListItem {
         QListItem {
-            name: item.name,
+            name: item.name.clone(),
+            markup: highlight(&item.name, &item.matches, highlight_color),
             id: item.id,
-            icon: item.icon,
+            icon: item.icon.clone(),
+            subtitle: item.subtitle.clone(),
+            source: item.source.clone(),
+            badges: item
+                .badges
+                .iter()
+                .map(|badge| badge.label())
+                .collect::<Vec<_>>()
+                .join(","),
         }
     }
 }
 
+/// Mark up `text` with the characters in `matches` bold and colored.
+fn highlight(text: &str, matches: &[Range<usize>], color: &str) -> String {
+    let mut markup = String::new();
+    let mut matches = matches.iter().peekable();
+    for (idx, c) in text.chars().enumerate() {
+        if matches.peek().map_or(false, |range| range.start == idx) {
+            markup.push_str(&format!("<b><font color=\"{}\">", color));
+        }
+        match c {
+            '&' => markup.push_str("&amp;"),
+            '<' => markup.push_str("&lt;"),
+            '>' => markup.push_str("&gt;"),
+            '"' => markup.push_str("&quot;"),
+            _ => markup.push(c),
+        }
+        if matches.peek().map_or(false, |range| range.end == idx + 1) {
+            markup.push_str("</font></b>");
+            matches.next();
+        }
+    }
+    markup
+}
+
 fn prepend_hash(mut s: String) -> String {
     match s.chars().nth(0) {
         Some(c) if c != '#' => {
@@ -383,6 +725,13 @@ fn prepend_hash(mut s: String) -> String {
 
 impl QMetaType for QListItem {}
 
+#[derive(Default, Clone, SimpleListItem)]
+struct QActionItem {
+    pub name: String,
+}
+
+impl QMetaType for QActionItem {}
+
 qrc!(init_qml_resources,
     "ui" {
         "ui/main.qml" as "main.qml",
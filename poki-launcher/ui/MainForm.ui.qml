@@ -42,11 +42,42 @@ Rectangle {
 		launcher.hide();
 	}
 
+	function run_action() {
+		if (launcher.run_action()) {
+			hide();
+		}
+	}
+
+	// Qt Quick has no clipboard API so copy through a hidden text field
+	TextEdit {
+		id: clipboard
+		visible: false
+	}
+
+	Connections {
+		target: launcher
+		onCopy_requested: {
+			clipboard.text = text;
+			clipboard.selectAll();
+			clipboard.copy();
+		}
+	}
+
 	Shortcut {
 		sequence: "F5"
 		onActivated: load()
 	}
 
+	Shortcut {
+		sequence: "Ctrl+L"
+		onActivated: aliasDialog.open()
+	}
+
+	Shortcut {
+		sequence: "Ctrl+P"
+		onActivated: launcher.toggle_pin()
+	}
+
     ColumnLayout {
         anchors.fill: parent
         anchors.margins: 0
@@ -60,21 +91,69 @@ Rectangle {
 			Layout.preferredHeight: window.height * launcher.input_box_ratio
 			Layout.alignment: Qt.AlignHCenter
 
+			Text {
+				id: mode_label
+				visible: launcher.mode != ""
+				text: launcher.mode + ":"
+				color: launcher.input_text_color
+				opacity: 0.6
+				font.pointSize: launcher.input_font_size || 13
+				anchors.left: input_box.left
+				anchors.leftMargin: 10
+				anchors.verticalCenter: input_box.verticalCenter
+			}
+
 			TextInput {
 				id: input
 				focus: true
+				anchors.left: mode_label.visible ? mode_label.right : input_box.left
 				color: launcher.input_text_color
 				padding: 10
 				anchors.verticalCenter: input_box.verticalCenter
 				// If the size isn't set initally Qt compains
 				font.pointSize: launcher.input_font_size || 13
 				onTextChanged: launcher.search(text)
-				Keys.onUpPressed: launcher.up()
-				Keys.onDownPressed: launcher.down()
-				Keys.onReturnPressed: run()
-				Keys.onEscapePressed: hide()
+				Keys.onUpPressed: launcher.actions_visible ? launcher.action_up() : launcher.up()
+				Keys.onDownPressed: launcher.actions_visible ? launcher.action_down() : launcher.down()
+				Keys.onReturnPressed: {
+					if (event.modifiers & Qt.ControlModifier) {
+						launcher.show_actions();
+					} else if (launcher.actions_visible) {
+						run_action();
+					} else {
+						run();
+					}
+				}
+				Keys.onEscapePressed: launcher.actions_visible ? launcher.hide_actions() : hide()
+				Keys.onRightPressed: {
+					if (cursorPosition == text.length && !launcher.actions_visible) {
+						launcher.show_actions();
+					} else {
+						event.accepted = false;
+					}
+				}
+				Keys.onLeftPressed: {
+					if (launcher.actions_visible) {
+						launcher.hide_actions();
+					} else {
+						event.accepted = false;
+					}
+				}
+				Keys.onTabPressed: launcher.next_mode()
+				Keys.onBacktabPressed: launcher.prev_mode()
 			}
 
+			Text {
+				id: correction
+				visible: launcher.correction != ""
+				text: launcher.correction
+				color: launcher.input_text_color
+				opacity: 0.6
+				font.pointSize: launcher.input_font_size || 13
+				anchors.right: load_ind.left
+				anchors.rightMargin: 10
+				anchors.verticalCenter: input_box.verticalCenter
+			}
 			BusyIndicator {
 				id: load_ind
 				running: launcher.loading
@@ -118,14 +197,65 @@ Rectangle {
 							source: "image://icon/" + icon
 						}
 
-						Text {
+						ColumnLayout {
 							Layout.alignment: Qt.AlignLeft
 							Layout.fillWidth: true
 							Layout.leftMargin: item.width * 0.05
-							color: launcher.app_text_color
-							text: name
-							// If the size isn't set initally Qt compains
-							font.pointSize: launcher.app_font_size || 20
+							spacing: 0
+
+							Text {
+								Layout.fillWidth: true
+								color: launcher.app_text_color
+								text: markup
+								textFormat: Text.StyledText
+								elide: Text.ElideRight
+								// If the size isn't set initally Qt compains
+								font.pointSize: launcher.app_font_size || 20
+							}
+
+							Text {
+								Layout.fillWidth: true
+								visible: subtitle != ""
+								color: launcher.app_text_color
+								opacity: 0.6
+								text: subtitle
+								textFormat: Text.PlainText
+								elide: Text.ElideMiddle
+								font.pointSize: (launcher.app_font_size || 20) * 0.5
+							}
+						}
+
+						Row {
+							Layout.alignment: Qt.AlignRight
+							Layout.rightMargin: item.width * 0.03
+							spacing: 4
+
+							Repeater {
+								model: badges == "" ? [] : badges.split(",")
+								delegate: Rectangle {
+									width: badge_text.width + 8
+									height: badge_text.height + 4
+									radius: 3
+									color: "transparent"
+									border.color: launcher.app_separator_color
+
+									Text {
+										id: badge_text
+										anchors.centerIn: parent
+										color: launcher.app_text_color
+										text: modelData
+										font.pointSize: (launcher.app_font_size || 20) * 0.4
+									}
+								}
+							}
+
+							Text {
+								visible: launcher.show_sources && source != ""
+								color: launcher.app_text_color
+								opacity: 0.6
+								text: source
+								font.pointSize: (launcher.app_font_size || 20) * 0.4
+							}
 						}
 					}
 				}
@@ -151,6 +281,53 @@ Rectangle {
         }
     }
 
+	// Actions of the selected item, opened with Ctrl+Enter or Right
+	Rectangle {
+		id: actions_box
+		visible: launcher.actions_visible
+		color: launcher.background_color
+		border.color: launcher.app_separator_color
+		border.width: 1
+		anchors.right: parent.right
+		anchors.bottom: parent.bottom
+		anchors.margins: 10
+		width: parent.width * 0.5
+		height: Math.min(actions_list.count, 6) * actions_list.row_height + 2
+
+		ListView {
+			id: actions_list
+			property real row_height: (launcher.app_font_size || 20) * 1.8
+			anchors.fill: parent
+			anchors.margins: 1
+			interactive: false
+			clip: true
+			model: launcher.actions_model
+			currentIndex: launcher.selected_action
+			delegate: Rectangle {
+				width: actions_list.width
+				height: actions_list.row_height
+				color: (index == launcher.selected_action) ? launcher.selected_app_color : launcher.background_color
+
+				Text {
+					anchors.verticalCenter: parent.verticalCenter
+					anchors.left: parent.left
+					anchors.leftMargin: 10
+					color: launcher.app_text_color
+					text: name
+					font.pointSize: (launcher.app_font_size || 20) * 0.7
+				}
+
+				MouseArea {
+					anchors.fill: parent
+					onClicked: {
+						launcher.selected_action = index
+						run_action()
+					}
+				}
+			}
+		}
+	}
+
 	MessageDialog {
 		id: errorDialog
 		title: "Error"
@@ -160,4 +337,26 @@ Rectangle {
 		detailedText: launcher.error_msg
 		standardButtons: StandardButton.Ok
 	}
+
+	Dialog {
+		id: aliasDialog
+		title: qsTr("Add alias")
+		standardButtons: StandardButton.Ok | StandardButton.Cancel
+
+		TextField {
+			id: aliasInput
+			width: parent.width
+			placeholderText: qsTr("Alias for the selected item")
+		}
+
+		onAccepted: {
+			launcher.add_alias(aliasInput.text)
+			aliasInput.text = ""
+			input.forceActiveFocus()
+		}
+		onRejected: {
+			aliasInput.text = ""
+			input.forceActiveFocus()
+		}
+	}
 }